@@ -0,0 +1,191 @@
+//! Interrupt-safe memory allocation.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::{fmt, ptr};
+
+use crate::{error, imp, Error};
+
+/// A [`GlobalAlloc`] wrapper that disables interrupts for each call.
+///
+/// Allocators that are not reentrant, like simple linked-list allocators behind a spinlock, can be corrupted or deadlock if an interrupt handler or signal handler allocates while thread code is allocating.
+/// This wrapper runs each call to the inner allocator with interrupts disabled.
+///
+/// Interrupts are disabled without creating a [`Guard`], since allocators must not unwind and should not have side effects.
+/// Thus, allocator calls are not recorded as critical sections and do not run hooks, deferred work, rescheduling, or `tracing` events.
+/// This never panics:
+/// if interrupts cannot be disabled, allocating fails by returning a null pointer and deallocating leaks the memory.
+/// Errors when restoring interrupts are handled like [`Guard`'s].
+///
+/// Optionally, allocations with interrupts already disabled can be reported via [`with_report`].
+/// Since interrupt handlers run with interrupts disabled, this detects allocations from interrupt handlers.
+///
+/// [`Guard`]: crate::Guard
+/// [`Guard`'s]: crate::Guard#errors-when-restoring
+/// [`with_report`]: Self::with_report
+///
+/// # Examples
+///
+/// ```
+/// use std::alloc::System;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: interrupts::alloc::Locked<System> = interrupts::alloc::Locked::new(System);
+/// ```
+pub struct Locked<A> {
+    inner: A,
+    report: Option<fn(Layout)>,
+}
+
+impl<A> Locked<A> {
+    /// Wraps `inner`.
+    pub const fn new(inner: A) -> Self {
+        Self {
+            inner,
+            report: None,
+        }
+    }
+
+    /// Wraps `inner`, calling `report` for each allocation with interrupts already disabled.
+    ///
+    /// `report` is called with the layout of the requested allocation before allocating.
+    /// This covers allocations from interrupt handlers but also from any other code running with interrupts disabled.
+    ///
+    /// On Unix, signal handlers usually run with only some signals blocked, so their allocations are not reported.
+    /// Only handlers installed with a full `sa_mask` or with `unix::set_handler` (`lazy-signals` feature) are covered.
+    /// `report` runs with interrupts disabled and must neither allocate nor panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::alloc::{Layout, System};
+    ///
+    /// fn report(_layout: Layout) {
+    ///     // allocating with interrupts disabled
+    /// }
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: interrupts::alloc::Locked<System> =
+    ///     interrupts::alloc::Locked::with_report(System, report);
+    /// ```
+    pub const fn with_report(inner: A, report: fn(Layout)) -> Self {
+        Self {
+            inner,
+            report: Some(report),
+        }
+    }
+
+    /// Returns a reference to the inner allocator.
+    pub const fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the inner allocator.
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    /// Runs `f` with interrupts disabled, reporting `layout` if they were already disabled.
+    ///
+    /// Returns `None` if interrupts could not be disabled.
+    #[inline]
+    fn locked<R>(&self, layout: Option<Layout>, f: impl FnOnce() -> R) -> Option<R> {
+        let flags = imp::read_disable().ok()?;
+
+        if let (Some(report), Some(layout)) = (self.report, layout) {
+            if !imp::were_enabled(flags) {
+                report(layout);
+            }
+        }
+
+        let ret = f();
+
+        if let Err(err) = imp::restore(flags) {
+            error::restore_failed(Error(err));
+        }
+
+        Some(ret)
+    }
+}
+
+impl<A: fmt::Debug> fmt::Debug for Locked<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Locked")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Locked<A> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.locked(Some(layout), || unsafe { self.inner.alloc(layout) })
+            .unwrap_or(ptr::null_mut())
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.locked(None, || unsafe { self.inner.dealloc(ptr, layout) });
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.locked(Some(layout), || unsafe { self.inner.alloc_zeroed(layout) })
+            .unwrap_or(ptr::null_mut())
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align(new_size, layout.align()).ok();
+        self.locked(new_layout, || unsafe {
+            self.inner.realloc(ptr, layout, new_size)
+        })
+        .unwrap_or(ptr::null_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::alloc::System;
+
+    use super::*;
+
+    std::thread_local! {
+        static REPORTED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Checked;
+
+    unsafe impl GlobalAlloc for Checked {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            assert!(!crate::are_enabled());
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            assert!(!crate::are_enabled());
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[test]
+    fn report() {
+        fn report(layout: Layout) {
+            assert_eq!(layout, Layout::new::<u64>());
+            REPORTED.set(REPORTED.get() + 1);
+        }
+
+        let alloc = Locked::with_report(Checked, report);
+        let layout = Layout::new::<u64>();
+
+        let ptr = unsafe { alloc.alloc(layout) };
+        assert!(!ptr.is_null());
+        assert_eq!(REPORTED.get(), 0);
+        unsafe { alloc.dealloc(ptr, layout) };
+
+        let ptr = crate::without(|| unsafe { alloc.alloc(layout) });
+        assert!(!ptr.is_null());
+        assert_eq!(REPORTED.get(), 1);
+        unsafe { alloc.dealloc(ptr, layout) };
+    }
+}
@@ -0,0 +1,636 @@
+//! Atomic types emulated by disabling interrupts.
+//!
+//! Some targets, like RISC-V without the A extension (e.g., `riscv32imc-unknown-none-elf`), lack atomic read-modify-write instructions.
+//! On single-core systems, read-modify-write operations can be made atomic by disabling interrupts instead.
+//! This module provides such types with the same API as [`core::sync::atomic`].
+//!
+//! The memory orderings are accepted for API compatibility but have no effect.
+//! On a single core, all operations are sequentially consistent.
+//!
+//! <div class="warning">
+//!
+//! Disabling interrupts only excludes other code on the current CPU.
+//! This module is unsound on systems with more than one CPU and on hosted targets, where other threads may run in parallel.
+//! Thus, it requires the `unsafe-assume-single-core` feature, which fails to compile on hosted and unsupported targets.
+//!
+//! </div>
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use core::sync::atomic::Ordering;
+//!
+//! use interrupts::atomic::AtomicUsize;
+//!
+//! static COUNTER: AtomicUsize = AtomicUsize::new(0);
+//!
+//! COUNTER.fetch_add(1, Ordering::Relaxed);
+//! assert_eq!(COUNTER.load(Ordering::Relaxed), 1);
+//! ```
+
+// Host unit tests run single-threaded code only.
+#[cfg(not(any(
+    test,
+    all(
+        target_os = "none",
+        any(
+            target_arch = "aarch64",
+            target_arch = "riscv32",
+            target_arch = "riscv64",
+            target_arch = "x86_64"
+        )
+    )
+)))]
+compile_error!("the `unsafe-assume-single-core` feature requires a supported bare-metal target");
+
+use core::cell::UnsafeCell;
+use core::fmt;
+pub use core::sync::atomic::Ordering;
+
+use crate::imp;
+
+/// Runs `f` with interrupts disabled.
+///
+/// This does not create a [`Guard`] to avoid recording each atomic operation as a critical section.
+///
+/// [`Guard`]: crate::Guard
+#[inline]
+fn critical<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    imp::without(f)
+}
+
+/// A boolean type which can be safely shared between threads.
+///
+/// See [`core::sync::atomic::AtomicBool`].
+#[repr(transparent)]
+pub struct AtomicBool {
+    v: UnsafeCell<bool>,
+}
+
+// SAFETY: All accesses happen with interrupts disabled on a single core.
+unsafe impl Sync for AtomicBool {}
+
+impl AtomicBool {
+    /// Creates a new `AtomicBool`.
+    #[inline]
+    pub const fn new(v: bool) -> Self {
+        Self {
+            v: UnsafeCell::new(v),
+        }
+    }
+
+    /// Returns a mutable reference to the underlying `bool`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut bool {
+        self.v.get_mut()
+    }
+
+    /// Consumes the atomic and returns the contained value.
+    #[inline]
+    pub fn into_inner(self) -> bool {
+        self.v.into_inner()
+    }
+
+    /// Returns a mutable pointer to the underlying `bool`.
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut bool {
+        self.v.get()
+    }
+
+    /// Loads a value from the bool.
+    #[inline]
+    pub fn load(&self, _order: Ordering) -> bool {
+        critical(|| unsafe { *self.v.get() })
+    }
+
+    /// Stores a value into the bool.
+    #[inline]
+    pub fn store(&self, val: bool, _order: Ordering) {
+        critical(|| unsafe { *self.v.get() = val });
+    }
+
+    /// Stores a value into the bool, returning the previous value.
+    #[inline]
+    pub fn swap(&self, val: bool, order: Ordering) -> bool {
+        self.fetch_update_infallible(order, |_| val)
+    }
+
+    /// Stores a value into the bool if the current value is the same as the `current` value.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: bool,
+        new: bool,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.fetch_update(success, failure, |v| (v == current).then_some(new))
+    }
+
+    /// Stores a value into the bool if the current value is the same as the `current` value.
+    ///
+    /// Unlike [`core::sync::atomic::AtomicBool::compare_exchange_weak`], this never fails spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: bool,
+        new: bool,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    /// Logical "and" with a boolean value, returning the previous value.
+    #[inline]
+    pub fn fetch_and(&self, val: bool, order: Ordering) -> bool {
+        self.fetch_update_infallible(order, |v| v & val)
+    }
+
+    /// Logical "nand" with a boolean value, returning the previous value.
+    #[inline]
+    pub fn fetch_nand(&self, val: bool, order: Ordering) -> bool {
+        self.fetch_update_infallible(order, |v| !(v & val))
+    }
+
+    /// Logical "or" with a boolean value, returning the previous value.
+    #[inline]
+    pub fn fetch_or(&self, val: bool, order: Ordering) -> bool {
+        self.fetch_update_infallible(order, |v| v | val)
+    }
+
+    /// Logical "xor" with a boolean value, returning the previous value.
+    #[inline]
+    pub fn fetch_xor(&self, val: bool, order: Ordering) -> bool {
+        self.fetch_update_infallible(order, |v| v ^ val)
+    }
+
+    /// Logical "not" with the current value, returning the previous value.
+    #[inline]
+    pub fn fetch_not(&self, order: Ordering) -> bool {
+        self.fetch_update_infallible(order, |v| !v)
+    }
+
+    /// Fetches the value, and applies a function to it that returns an optional new value.
+    ///
+    /// `f` runs with interrupts disabled and is called exactly once.
+    #[inline]
+    pub fn fetch_update<F>(
+        &self,
+        _set_order: Ordering,
+        _fetch_order: Ordering,
+        f: F,
+    ) -> Result<bool, bool>
+    where
+        F: FnOnce(bool) -> Option<bool>,
+    {
+        critical(|| {
+            // SAFETY: Interrupts are disabled on the only core.
+            let v = unsafe { &mut *self.v.get() };
+            let prev = *v;
+            match f(prev) {
+                Some(new) => {
+                    *v = new;
+                    Ok(prev)
+                }
+                None => Err(prev),
+            }
+        })
+    }
+
+    #[inline]
+    fn fetch_update_infallible(&self, order: Ordering, f: impl FnOnce(bool) -> bool) -> bool {
+        match self.fetch_update(order, order, |v| Some(f(v))) {
+            Ok(v) | Err(v) => v,
+        }
+    }
+}
+
+impl Default for AtomicBool {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl From<bool> for AtomicBool {
+    fn from(v: bool) -> Self {
+        Self::new(v)
+    }
+}
+
+impl fmt::Debug for AtomicBool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+/// A raw pointer type which can be safely shared between threads.
+///
+/// See [`core::sync::atomic::AtomicPtr`].
+#[repr(transparent)]
+pub struct AtomicPtr<T> {
+    p: UnsafeCell<*mut T>,
+}
+
+// SAFETY: All accesses happen with interrupts disabled on a single core.
+unsafe impl<T> Send for AtomicPtr<T> {}
+// SAFETY: All accesses happen with interrupts disabled on a single core.
+unsafe impl<T> Sync for AtomicPtr<T> {}
+
+impl<T> AtomicPtr<T> {
+    /// Creates a new `AtomicPtr`.
+    #[inline]
+    pub const fn new(p: *mut T) -> Self {
+        Self {
+            p: UnsafeCell::new(p),
+        }
+    }
+
+    /// Returns a mutable reference to the underlying pointer.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut *mut T {
+        self.p.get_mut()
+    }
+
+    /// Consumes the atomic and returns the contained value.
+    #[inline]
+    pub fn into_inner(self) -> *mut T {
+        self.p.into_inner()
+    }
+
+    /// Returns a mutable pointer to the underlying pointer.
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut *mut T {
+        self.p.get()
+    }
+
+    /// Loads a value from the pointer.
+    #[inline]
+    pub fn load(&self, _order: Ordering) -> *mut T {
+        critical(|| unsafe { *self.p.get() })
+    }
+
+    /// Stores a value into the pointer.
+    #[inline]
+    pub fn store(&self, ptr: *mut T, _order: Ordering) {
+        critical(|| unsafe { *self.p.get() = ptr });
+    }
+
+    /// Stores a value into the pointer, returning the previous value.
+    #[inline]
+    pub fn swap(&self, ptr: *mut T, order: Ordering) -> *mut T {
+        match self.fetch_update(order, order, |_| Some(ptr)) {
+            Ok(p) | Err(p) => p,
+        }
+    }
+
+    /// Stores a value into the pointer if the current value is the same as the `current` value.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*mut T, *mut T> {
+        self.fetch_update(success, failure, |p| (p == current).then_some(new))
+    }
+
+    /// Stores a value into the pointer if the current value is the same as the `current` value.
+    ///
+    /// Unlike [`core::sync::atomic::AtomicPtr::compare_exchange_weak`], this never fails spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*mut T, *mut T> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    /// Fetches the value, and applies a function to it that returns an optional new value.
+    ///
+    /// `f` runs with interrupts disabled and is called exactly once.
+    #[inline]
+    pub fn fetch_update<F>(
+        &self,
+        _set_order: Ordering,
+        _fetch_order: Ordering,
+        f: F,
+    ) -> Result<*mut T, *mut T>
+    where
+        F: FnOnce(*mut T) -> Option<*mut T>,
+    {
+        critical(|| {
+            // SAFETY: Interrupts are disabled on the only core.
+            let p = unsafe { &mut *self.p.get() };
+            let prev = *p;
+            match f(prev) {
+                Some(new) => {
+                    *p = new;
+                    Ok(prev)
+                }
+                None => Err(prev),
+            }
+        })
+    }
+}
+
+impl<T> Default for AtomicPtr<T> {
+    fn default() -> Self {
+        Self::new(core::ptr::null_mut())
+    }
+}
+
+impl<T> From<*mut T> for AtomicPtr<T> {
+    fn from(p: *mut T) -> Self {
+        Self::new(p)
+    }
+}
+
+impl<T> fmt::Debug for AtomicPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+macro_rules! atomic_int {
+    ($atomic:ident, $int:ty) => {
+        /// An integer type which can be safely shared between threads.
+        ///
+        #[doc = concat!("See [`core::sync::atomic::", stringify!($atomic), "`].")]
+        #[repr(transparent)]
+        pub struct $atomic {
+            v: UnsafeCell<$int>,
+        }
+
+        // SAFETY: All accesses happen with interrupts disabled on a single core.
+        unsafe impl Sync for $atomic {}
+
+        impl $atomic {
+            #[doc = concat!("Creates a new `", stringify!($atomic), "`.")]
+            #[inline]
+            pub const fn new(v: $int) -> Self {
+                Self {
+                    v: UnsafeCell::new(v),
+                }
+            }
+
+            /// Returns a mutable reference to the underlying integer.
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut $int {
+                self.v.get_mut()
+            }
+
+            /// Consumes the atomic and returns the contained value.
+            #[inline]
+            pub fn into_inner(self) -> $int {
+                self.v.into_inner()
+            }
+
+            /// Returns a mutable pointer to the underlying integer.
+            #[inline]
+            pub const fn as_ptr(&self) -> *mut $int {
+                self.v.get()
+            }
+
+            /// Loads a value from the atomic integer.
+            #[inline]
+            pub fn load(&self, _order: Ordering) -> $int {
+                critical(|| unsafe { *self.v.get() })
+            }
+
+            /// Stores a value into the atomic integer.
+            #[inline]
+            pub fn store(&self, val: $int, _order: Ordering) {
+                critical(|| unsafe { *self.v.get() = val });
+            }
+
+            /// Stores a value into the atomic integer, returning the previous value.
+            #[inline]
+            pub fn swap(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |_| val)
+            }
+
+            /// Stores a value into the atomic integer if the current value is the same as the `current` value.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.fetch_update(success, failure, |v| (v == current).then_some(new))
+            }
+
+            /// Stores a value into the atomic integer if the current value is the same as the `current` value.
+            ///
+            /// Unlike the [`core::sync::atomic`] version, this never fails spuriously.
+            #[inline]
+            pub fn compare_exchange_weak(
+                &self,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                self.compare_exchange(current, new, success, failure)
+            }
+
+            /// Adds to the current value, returning the previous value.
+            ///
+            /// This operation wraps around on overflow.
+            #[inline]
+            pub fn fetch_add(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| v.wrapping_add(val))
+            }
+
+            /// Subtracts from the current value, returning the previous value.
+            ///
+            /// This operation wraps around on overflow.
+            #[inline]
+            pub fn fetch_sub(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| v.wrapping_sub(val))
+            }
+
+            /// Bitwise "and" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_and(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| v & val)
+            }
+
+            /// Bitwise "nand" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_nand(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| !(v & val))
+            }
+
+            /// Bitwise "or" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_or(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| v | val)
+            }
+
+            /// Bitwise "xor" with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_xor(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| v ^ val)
+            }
+
+            /// Maximum with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_max(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| v.max(val))
+            }
+
+            /// Minimum with the current value, returning the previous value.
+            #[inline]
+            pub fn fetch_min(&self, val: $int, order: Ordering) -> $int {
+                self.fetch_update_infallible(order, |v| v.min(val))
+            }
+
+            /// Fetches the value, and applies a function to it that returns an optional new value.
+            ///
+            /// `f` runs with interrupts disabled and is called exactly once.
+            #[inline]
+            pub fn fetch_update<F>(
+                &self,
+                _set_order: Ordering,
+                _fetch_order: Ordering,
+                f: F,
+            ) -> Result<$int, $int>
+            where
+                F: FnOnce($int) -> Option<$int>,
+            {
+                critical(|| {
+                    // SAFETY: Interrupts are disabled on the only core.
+                    let v = unsafe { &mut *self.v.get() };
+                    let prev = *v;
+                    match f(prev) {
+                        Some(new) => {
+                            *v = new;
+                            Ok(prev)
+                        }
+                        None => Err(prev),
+                    }
+                })
+            }
+
+            #[inline]
+            fn fetch_update_infallible(
+                &self,
+                order: Ordering,
+                f: impl FnOnce($int) -> $int,
+            ) -> $int {
+                match self.fetch_update(order, order, |v| Some(f(v))) {
+                    Ok(v) | Err(v) => v,
+                }
+            }
+        }
+
+        impl Default for $atomic {
+            fn default() -> Self {
+                Self::new(0)
+            }
+        }
+
+        impl From<$int> for $atomic {
+            fn from(v: $int) -> Self {
+                Self::new(v)
+            }
+        }
+
+        impl fmt::Debug for $atomic {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+            }
+        }
+    };
+}
+
+atomic_int!(AtomicI8, i8);
+atomic_int!(AtomicU8, u8);
+atomic_int!(AtomicI16, i16);
+atomic_int!(AtomicU16, u16);
+atomic_int!(AtomicI32, i32);
+atomic_int!(AtomicU32, u32);
+atomic_int!(AtomicI64, i64);
+atomic_int!(AtomicU64, u64);
+atomic_int!(AtomicIsize, isize);
+atomic_int!(AtomicUsize, usize);
+
+#[cfg(test)]
+mod tests {
+    use core::ptr;
+
+    use super::*;
+
+    #[test]
+    fn bool() {
+        let a = AtomicBool::new(false);
+        assert!(!a.swap(true, Ordering::SeqCst));
+        assert_eq!(
+            a.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst),
+            Err(true)
+        );
+        assert_eq!(
+            a.compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst),
+            Ok(true)
+        );
+        assert!(!a.fetch_or(true, Ordering::SeqCst));
+        assert!(a.fetch_xor(true, Ordering::SeqCst));
+        assert!(!a.fetch_not(Ordering::SeqCst));
+        assert!(a.fetch_nand(true, Ordering::SeqCst));
+        assert!(!a.into_inner());
+        assert!(crate::are_enabled());
+    }
+
+    #[test]
+    fn int() {
+        let a = AtomicU8::new(250);
+        assert_eq!(a.fetch_add(10, Ordering::SeqCst), 250);
+        assert_eq!(a.load(Ordering::SeqCst), 4);
+        assert_eq!(a.fetch_sub(5, Ordering::SeqCst), 4);
+        assert_eq!(a.fetch_max(7, Ordering::SeqCst), 255);
+        assert_eq!(a.fetch_min(7, Ordering::SeqCst), 255);
+        assert_eq!(a.swap(1, Ordering::SeqCst), 7);
+        assert_eq!(
+            a.compare_exchange(2, 3, Ordering::SeqCst, Ordering::SeqCst),
+            Err(1)
+        );
+        assert_eq!(
+            a.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| v.checked_mul(2)),
+            Ok(1)
+        );
+        assert_eq!(
+            a.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |_| None),
+            Err(2)
+        );
+        assert_eq!(a.into_inner(), 2);
+        assert!(crate::are_enabled());
+    }
+
+    #[test]
+    fn ptr() {
+        let mut x = 1;
+        let mut y = 2;
+        let a = AtomicPtr::new(ptr::null_mut());
+        assert!(a.swap(&mut x, Ordering::SeqCst).is_null());
+        assert_eq!(
+            a.compare_exchange(&mut y, &mut y, Ordering::SeqCst, Ordering::SeqCst),
+            Err(&mut x as *mut _)
+        );
+        assert_eq!(
+            a.compare_exchange(&mut x, &mut y, Ordering::SeqCst, Ordering::SeqCst),
+            Ok(&mut x as *mut _)
+        );
+        assert_eq!(a.into_inner(), &mut y as *mut _);
+        assert!(crate::are_enabled());
+    }
+}
@@ -0,0 +1,116 @@
+//! An implementation of [`critical_section::Impl`].
+//!
+//! On bare-metal targets, [`imp::Flags`] is used as [`RawRestoreState`] directly.
+//! This only disables interrupts on the current CPU and is thus only sound on single-core systems.
+//! With the `soft-disable` feature, interrupts are masked in hardware, since [`RawRestoreState`] has to fit the hardware flags.
+//!
+//! On Unix, signals are blocked and a global lock is taken, since blocking signals does not exclude other threads.
+//! [`RawRestoreState`] indicates whether the critical section was entered or nested.
+
+use crate::imp;
+
+cfg_if::cfg_if! {
+    if #[cfg(all(unix, not(miri)))] {
+        use core::cell::{Cell, UnsafeCell};
+        use core::mem::MaybeUninit;
+        use std::sync::{Mutex, MutexGuard, PoisonError};
+
+        use ::critical_section::RawRestoreState;
+
+        use crate::{error, Error};
+
+        struct CriticalSection;
+        ::critical_section::set_impl!(CriticalSection);
+
+        static LOCK: Mutex<()> = Mutex::new(());
+
+        /// The state of the current critical section, protected by [`LOCK`].
+        struct State(UnsafeCell<MaybeUninit<(MutexGuard<'static, ()>, imp::Flags)>>);
+
+        // SAFETY: Only accessed by the thread holding `LOCK`.
+        unsafe impl Sync for State {}
+
+        static STATE: State = State(UnsafeCell::new(MaybeUninit::uninit()));
+
+        std::thread_local! {
+            static IS_LOCKED: Cell<bool> = const { Cell::new(false) };
+        }
+
+        unsafe impl ::critical_section::Impl for CriticalSection {
+            unsafe fn acquire() -> RawRestoreState {
+                if IS_LOCKED.get() {
+                    return false;
+                }
+
+                let flags = match imp::read_disable() {
+                    Ok(flags) => flags,
+                    Err(err) => panic!("failed to disable interrupts: {}", Error(err)),
+                };
+                let guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+                IS_LOCKED.set(true);
+                unsafe {
+                    (*STATE.0.get()).write((guard, flags));
+                }
+
+                true
+            }
+
+            unsafe fn release(restore_state: RawRestoreState) {
+                if !restore_state {
+                    return;
+                }
+
+                let (guard, flags) = unsafe { (*STATE.0.get()).assume_init_read() };
+                IS_LOCKED.set(false);
+                drop(guard);
+                if let Err(err) = imp::restore(flags) {
+                    error::restore_failed(Error(err));
+                }
+            }
+        }
+    } else if #[cfg(all(
+        target_os = "none",
+        any(
+            target_arch = "aarch64",
+            target_arch = "riscv32",
+            target_arch = "riscv64",
+            target_arch = "x86_64"
+        )
+    ))] {
+        use ::critical_section::RawRestoreState;
+
+        #[cfg(feature = "soft-disable")]
+        use imp::hard as backend;
+        #[cfg(not(feature = "soft-disable"))]
+        use imp as backend;
+
+        struct CriticalSection;
+        ::critical_section::set_impl!(CriticalSection);
+
+        unsafe impl ::critical_section::Impl for CriticalSection {
+            unsafe fn acquire() -> RawRestoreState {
+                let Ok(flags) = backend::read_disable();
+                flags
+            }
+
+            unsafe fn release(restore_state: RawRestoreState) {
+                let Ok(()) = backend::restore(restore_state);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix, not(miri)))]
+mod tests {
+    #[test]
+    fn critical_section() {
+        ::critical_section::with(|_cs| {
+            assert!(!crate::are_enabled());
+            ::critical_section::with(|_cs| {
+                assert!(!crate::are_enabled());
+            });
+            assert!(!crate::are_enabled());
+        });
+        assert!(crate::are_enabled());
+    }
+}
@@ -0,0 +1,153 @@
+use core::fmt;
+
+use crate::hook::Hook;
+use crate::local;
+
+/// An error indicating that a [`Guard`] was dropped out of order.
+///
+/// Guards are identified by their nesting depth on the current CPU, starting at 0 for the outermost guard.
+/// After a violation, the guards that were nested inside the dropped guard are no longer checked.
+///
+/// [`Guard`]: crate::Guard
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DropOrderError {
+    expected: usize,
+    found: usize,
+}
+
+impl DropOrderError {
+    /// Returns the depth of the innermost guard, which should have been dropped.
+    pub fn expected(&self) -> usize {
+        self.expected
+    }
+
+    /// Returns the depth of the guard that was actually dropped.
+    pub fn found(&self) -> usize {
+        self.found
+    }
+}
+
+impl fmt::Display for DropOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "interrupt guard at depth {} was dropped while the guard at depth {} is still held",
+            self.found, self.expected
+        )
+    }
+}
+
+impl core::error::Error for DropOrderError {}
+
+static DROP_ORDER_HOOK: Hook<fn(DropOrderError)> = Hook::new();
+
+/// Set the hook that is called when a [`Guard`] is dropped out of order.
+///
+/// By default, dropping a guard out of order panics.
+/// If a hook is set, it is called with the error instead.
+/// Pass `None` to restore the default behavior.
+///
+/// The hook is called after the interrupt state has been restored.
+///
+/// [`Guard`]: crate::Guard
+///
+/// # Examples
+///
+/// ```
+/// fn report(err: interrupts::DropOrderError) {
+///     eprintln!("{err}");
+/// }
+///
+/// interrupts::set_drop_order_hook(Some(report));
+/// ```
+pub fn set_drop_order_hook(hook: Option<fn(DropOrderError)>) {
+    DROP_ORDER_HOOK.set(hook);
+}
+
+/// Pushes a new guard, returning its depth.
+#[inline]
+pub(crate) fn push() -> usize {
+    local::with(|local| {
+        let depth = local.depth.get();
+        local.depth.set(depth + 1);
+        depth
+    })
+}
+
+/// Pops the guard with the given depth.
+///
+/// Returns an error if an outer guard is dropped before an inner one.
+/// In that case, the inner guards are forgotten, so that dropping them later does not report further errors.
+#[inline]
+pub(crate) fn pop(depth: usize) -> Option<DropOrderError> {
+    local::with(|local| {
+        let held = local.depth.get();
+        if depth >= held {
+            // This guard has already been forgotten after an earlier violation.
+            return None;
+        }
+
+        local.depth.set(depth);
+        let expected = held - 1;
+        (depth != expected).then_some(DropOrderError {
+            expected,
+            found: depth,
+        })
+    })
+}
+
+/// Reports a drop order violation.
+///
+/// This runs after the interrupt state has been restored.
+#[cold]
+pub(crate) fn violated(err: DropOrderError) {
+    match DROP_ORDER_HOOK.get() {
+        Some(hook) => hook(err),
+        None => panic!("{err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn drop_order() {
+        static VIOLATIONS: AtomicUsize = AtomicUsize::new(0);
+
+        fn report(err: DropOrderError) {
+            assert_eq!(err.found(), 0);
+            assert_eq!(err.expected(), 1);
+            VIOLATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        set_drop_order_hook(Some(report));
+
+        let a = crate::disable();
+        let b = crate::disable();
+        drop(b);
+        drop(a);
+        assert_eq!(VIOLATIONS.load(Ordering::Relaxed), 0);
+
+        let a = crate::disable();
+        let b = crate::disable();
+        drop(a);
+        assert_eq!(VIOLATIONS.load(Ordering::Relaxed), 1);
+        drop(b);
+        assert_eq!(VIOLATIONS.load(Ordering::Relaxed), 1);
+        assert!(crate::are_enabled());
+
+        set_drop_order_hook(None);
+
+        let res = std::panic::catch_unwind(|| {
+            let a = crate::disable();
+            let _b = crate::disable();
+            drop(a);
+        });
+        assert!(res.is_err());
+        assert!(crate::are_enabled());
+        assert_eq!(local::with(|local| local.depth.get()), 0);
+    }
+}
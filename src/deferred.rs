@@ -0,0 +1,226 @@
+use core::cell::Cell;
+use core::fmt;
+
+use crate::{imp, local};
+
+/// The number of functions that can be deferred per CPU.
+const CAPACITY: usize = 32;
+
+/// An error indicating that work could not be deferred on the current CPU.
+///
+/// This happens if the deferred work queue of the current CPU is full or, on bare-metal targets, if no `CpuLocal` state has been registered.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DeferError {
+    kind: DeferErrorKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DeferErrorKind {
+    Full,
+    NoCpuLocal,
+}
+
+impl fmt::Display for DeferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            DeferErrorKind::Full => f.write_str("deferred work queue is full"),
+            DeferErrorKind::NoCpuLocal => f.write_str("no CPU-local state has been registered"),
+        }
+    }
+}
+
+impl core::error::Error for DeferError {}
+
+type Slot = Cell<Option<fn()>>;
+
+/// Per-CPU queue of deferred functions.
+///
+/// Only accessed with interrupts disabled.
+pub(crate) struct Queue {
+    buf: [Slot; CAPACITY],
+    head: Cell<usize>,
+    len: Cell<usize>,
+}
+
+impl Queue {
+    pub const fn new() -> Self {
+        Self {
+            buf: [const { Cell::new(None) }; CAPACITY],
+            head: Cell::new(0),
+            len: Cell::new(0),
+        }
+    }
+
+    fn push(&self, f: fn()) -> Result<(), DeferError> {
+        let len = self.len.get();
+        if len == CAPACITY {
+            return Err(DeferError {
+                kind: DeferErrorKind::Full,
+            });
+        }
+
+        self.buf[(self.head.get() + len) % CAPACITY].set(Some(f));
+        self.len.set(len + 1);
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    fn pop(&self) -> Option<fn()> {
+        let len = self.len.get();
+        if len == 0 {
+            return None;
+        }
+
+        let head = self.head.get();
+        self.head.set((head + 1) % CAPACITY);
+        self.len.set(len - 1);
+        self.buf[head].take()
+    }
+}
+
+/// Defer `f` until interrupts are enabled again.
+///
+/// If interrupts are disabled, `f` is enqueued into a fixed-capacity queue of the current CPU.
+/// The queue is drained once the outermost [`Guard`] is dropped, after interrupts have been restored.
+/// If interrupts are enabled, `f` is run immediately.
+///
+/// This may also be called from interrupt handlers.
+/// If the handler did not interrupt a critical section, the work runs once the next outermost [`Guard`] is dropped on this CPU or when [`run_deferred`] is called.
+///
+/// On Unix, interrupts count as disabled only if all signals are blocked.
+/// Signal handlers usually run with only some signals blocked, so calling this from such a handler runs `f` immediately, inside the handler.
+/// To defer work from a signal handler, install it with a full `sa_mask` or with `unix::set_handler` (`lazy-signals` feature), or disable interrupts in the handler before calling this.
+///
+/// # Errors
+///
+/// Returns an error if the queue of the current CPU is full.
+/// The queue holds up to 32 functions.
+///
+/// On bare-metal targets, also returns an error if `set_cpu_local` has not been called and the `unsafe-assume-single-core` feature is disabled.
+/// A queue shared by all CPUs would race.
+///
+/// [`Guard`]: crate::Guard
+///
+/// # Examples
+///
+/// ```
+/// fn work() {
+///     // interrupts are enabled
+/// }
+///
+/// interrupts::without(|| {
+///     // interrupts are disabled
+///     interrupts::defer(work).unwrap();
+/// });
+/// // `work` has run
+/// ```
+pub fn defer(f: fn()) -> Result<(), DeferError> {
+    if imp::are_enabled() {
+        f();
+        return Ok(());
+    }
+
+    local::try_with(|local| local.deferred.push(f)).unwrap_or(Err(DeferError {
+        kind: DeferErrorKind::NoCpuLocal,
+    }))
+}
+
+/// Run all functions that have been deferred on the current CPU.
+///
+/// This is done automatically when the outermost [`Guard`] is dropped, except in Unix signal handlers that run with signals partially blocked.
+/// Call this, for example, when returning from an interrupt handler to code that is not in a critical section.
+///
+/// This should be called with interrupts enabled, since deferred functions expect to run with interrupts enabled.
+///
+/// [`Guard`]: crate::Guard
+pub fn run_deferred() {
+    // Checking without disabling interrupts avoids the cost of disabling them in the common case.
+    // An interrupt handler might enqueue work right after this check, which then waits for the next drain.
+    if local::try_with(|local| local.deferred.is_empty()).unwrap_or(true) {
+        return;
+    }
+
+    while let Some(f) = pop() {
+        f();
+    }
+}
+
+/// Pops the next deferred function with interrupts disabled.
+///
+/// This runs when dropping guards and thus must not panic.
+/// If interrupts cannot be disabled, the remaining work stays queued.
+fn pop() -> Option<fn()> {
+    imp::try_without(|| local::try_with(|local| local.deferred.pop()).flatten())
+        .ok()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    std::thread_local! {
+        static RAN: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn work() {
+        assert!(crate::are_enabled());
+        RAN.set(RAN.get() + 1);
+    }
+
+    #[test]
+    fn deferred() {
+        crate::without(|| {
+            defer(work).unwrap();
+            crate::without(|| defer(work).unwrap());
+            assert_eq!(RAN.get(), 0);
+        });
+        assert_eq!(RAN.get(), 2);
+
+        defer(work).unwrap();
+        assert_eq!(RAN.get(), 3);
+    }
+
+    #[cfg(not(feature = "lazy-signals"))]
+    #[test]
+    fn signal_handler() {
+        use nix::libc;
+        use nix::sys::signal::{self, SigHandler, Signal};
+
+        extern "C" fn handle_sigxfsz(_signal: libc::c_int) {
+            // SIGXFSZ is still blocked, so the work must not run here.
+            crate::without(|| defer(work).unwrap());
+            assert_eq!(RAN.get(), 0);
+        }
+
+        let handler = SigHandler::Handler(handle_sigxfsz);
+        unsafe { signal::signal(Signal::SIGXFSZ, handler) }.unwrap();
+
+        signal::raise(Signal::SIGXFSZ).unwrap();
+        assert_eq!(RAN.get(), 0);
+        crate::without(|| {});
+        assert_eq!(RAN.get(), 1);
+    }
+
+    #[test]
+    fn full() {
+        fn nop() {}
+
+        let guard = crate::disable();
+        for _ in 0..CAPACITY {
+            defer(nop).unwrap();
+        }
+        assert_eq!(
+            defer(nop),
+            Err(DeferError {
+                kind: DeferErrorKind::Full
+            })
+        );
+        drop(guard);
+    }
+}
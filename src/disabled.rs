@@ -0,0 +1,76 @@
+//! Proof that interrupts are disabled.
+//!
+//! Functions that must only run with interrupts disabled can take a [`Token`] instead of creating a fresh [`Guard`].
+//! Since a token borrows the guard it was created from, it cannot outlive it.
+//!
+//! [`Guard`]: crate::Guard
+//!
+//! # Examples
+//!
+//! ```
+//! use interrupts::disabled::Token;
+//!
+//! fn requires_disabled(_token: Token<'_>) {
+//!     // interrupts are disabled
+//! }
+//!
+//! let guard = interrupts::disable();
+//! requires_disabled(guard.token());
+//! drop(guard);
+//! ```
+
+use core::marker::PhantomData;
+
+/// A token proving that interrupts are disabled for `'a`.
+///
+/// Created using [`Guard::token`], [`NestedGuard::token`], or [`without_token`].
+/// Also available as [`CriticalSection`].
+///
+/// This token is zero-sized and neither [`Send`] nor [`Sync`], since interrupts are only disabled on the current CPU.
+///
+/// [`Guard::token`]: crate::Guard::token
+/// [`NestedGuard::token`]: crate::NestedGuard::token
+/// [`without_token`]: crate::without_token
+/// [`CriticalSection`]: crate::CriticalSection
+///
+/// # Examples
+///
+/// A token cannot outlive its guard:
+///
+/// ```compile_fail
+/// let guard = interrupts::disable();
+/// let token = guard.token();
+/// drop(guard);
+/// let _token = token;
+/// ```
+///
+/// A token cannot be sent to other threads:
+///
+/// ```compile_fail
+/// fn send<T: Send>(_: T) {}
+///
+/// let guard = interrupts::disable();
+/// send(guard.token());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Token<'a> {
+    _guard: PhantomData<&'a ()>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl Token<'_> {
+    /// Creates a token without a guard.
+    ///
+    /// This is useful in interrupt handlers, which run with interrupts disabled.
+    ///
+    /// # Safety
+    ///
+    /// Interrupts must be disabled and must not be enabled for the lifetime of the token.
+    #[inline]
+    pub unsafe fn new_unchecked() -> Self {
+        Self {
+            _guard: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use core::fmt;
+
+use crate::hook::Hook;
+use crate::imp;
+
+/// An error that occurred while disabling or restoring interrupts.
+///
+/// Only the Unix backend can fail, for example, if `pthread_sigmask` is rejected by a seccomp filter.
+/// Bare-metal backends never return this error.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Error(pub(crate) imp::Error);
+
+#[cfg(all(unix, not(miri)))]
+impl Error {
+    /// Returns the underlying error number.
+    ///
+    /// [`Errno`] is part of [`nix`] 0.29, which is thus a public dependency of this crate.
+    /// Upgrading `nix` to an incompatible version is a breaking change.
+    ///
+    /// [`Errno`]: nix::errno::Errno
+    /// [`nix`]: https://crates.io/crates/nix
+    pub fn errno(&self) -> nix::errno::Errno {
+        self.0
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl core::error::Error for Error {}
+
+static RESTORE_ERROR_HOOK: Hook<fn(Error)> = Hook::new();
+
+/// Set the hook that is called when restoring interrupts fails.
+///
+/// Guards restore interrupts when they are dropped, where errors cannot be returned.
+/// By default, failing to restore interrupts aborts the process, since continuing with an unexpected interrupt state is unsound for code relying on [`Guard`]s.
+/// Panicking is not an option either, since [`Guard`]s are also dropped during unwinding.
+///
+/// If a hook is set, it is called with the error instead and execution continues.
+/// The interrupt state is left as is in that case.
+/// Pass `None` to restore the default behavior.
+///
+/// Since bare-metal backends never fail, the hook is never called on those.
+///
+/// [`Guard`]: crate::Guard
+///
+/// # Examples
+///
+/// ```
+/// fn report(err: interrupts::Error) {
+///     eprintln!("failed to restore interrupts: {err}");
+/// }
+///
+/// interrupts::set_restore_error_hook(Some(report));
+/// ```
+pub fn set_restore_error_hook(hook: Option<fn(Error)>) {
+    RESTORE_ERROR_HOOK.set(hook);
+}
+
+#[cold]
+pub(crate) fn restore_failed(err: Error) {
+    if let Some(hook) = RESTORE_ERROR_HOOK.get() {
+        return hook(err);
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "none")] {
+            match err.0 {}
+        } else {
+            std::eprintln!("interrupts: failed to restore interrupts: {err}");
+            std::process::abort();
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Futures that are polled with interrupts disabled.
+//!
+//! # Examples
+//!
+//! ```
+//! use interrupts::future::FutureExt;
+//!
+//! async fn work() {
+//!     // interrupts are disabled while polling
+//! }
+//!
+//! let future = work().without_interrupts();
+//! ```
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// A future that disables interrupts while polling the inner future.
+///
+/// Created using [`FutureExt::without_interrupts`].
+///
+/// Interrupts are disabled for each call to [`poll`] and restored before it returns.
+/// Between polls, interrupts are restored to the previous state, which allows the executor to wait for interrupts.
+///
+/// Since no [`Guard`] is held across polls, this future is [`Send`] if the inner future is.
+///
+/// [`poll`]: Future::poll
+/// [`Guard`]: crate::Guard
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithoutInterrupts<F> {
+    future: F,
+}
+
+impl<F> WithoutInterrupts<F> {
+    /// Wraps `future` to be polled with interrupts disabled.
+    pub const fn new(future: F) -> Self {
+        Self { future }
+    }
+
+    /// Consumes this future, returning the inner future.
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F: Future> Future for WithoutInterrupts<F> {
+    type Output = F::Output;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let guard = crate::disable();
+
+        // SAFETY: `future` is structurally pinned and never moved out of a pinned `self`.
+        let future = unsafe { self.map_unchecked_mut(|this| &mut this.future) };
+        let ret = future.poll(cx);
+
+        drop(guard);
+
+        ret
+    }
+}
+
+/// An extension trait for [`Future`]s.
+pub trait FutureExt: Future {
+    /// Poll this future with interrupts disabled.
+    ///
+    /// See [`WithoutInterrupts`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interrupts::future::FutureExt;
+    ///
+    /// async fn work() {
+    ///     // interrupts are disabled while polling
+    /// }
+    ///
+    /// let future = work().without_interrupts();
+    /// ```
+    fn without_interrupts(self) -> WithoutInterrupts<Self>
+    where
+        Self: Sized,
+    {
+        WithoutInterrupts::new(self)
+    }
+}
+
+impl<F: Future> FutureExt for F {}
+
+#[cfg(all(test, unix, not(miri), not(feature = "lazy-signals")))]
+mod tests {
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::task::Waker;
+
+    use nix::libc;
+    use nix::sys::signal::{self, SigHandler, Signal};
+
+    use super::*;
+
+    #[test]
+    fn signals() {
+        static HANDLER_RAN: AtomicBool = AtomicBool::new(false);
+
+        extern "C" fn handle_sigterm(_signal: libc::c_int) {
+            HANDLER_RAN.store(true, Ordering::Relaxed);
+        }
+
+        let handler = SigHandler::Handler(handle_sigterm);
+        unsafe { signal::signal(Signal::SIGTERM, handler) }.unwrap();
+
+        let mut polled = false;
+        let future = core::future::poll_fn(|_cx| {
+            assert!(!crate::are_enabled());
+            if polled {
+                return Poll::Ready(());
+            }
+            polled = true;
+            signal::raise(Signal::SIGTERM).unwrap();
+            assert!(!HANDLER_RAN.load(Ordering::Relaxed));
+            Poll::Pending
+        });
+
+        let mut future = pin!(future.without_interrupts());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        assert!(crate::are_enabled());
+        assert!(HANDLER_RAN.load(Ordering::Relaxed));
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(()));
+    }
+}
@@ -0,0 +1,49 @@
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::{mem, ptr};
+
+/// Function pointer types.
+///
+/// # Safety
+///
+/// Implementors must be function pointers.
+pub unsafe trait FnPtr: Copy {}
+
+unsafe impl<R> FnPtr for fn() -> R {}
+unsafe impl<A, R> FnPtr for fn(A) -> R {}
+
+/// A function pointer that can be registered at runtime.
+///
+/// This does not allocate and can be placed in a `static`.
+pub struct Hook<F> {
+    ptr: AtomicPtr<()>,
+    _f: PhantomData<F>,
+}
+
+impl<F: FnPtr> Hook<F> {
+    pub const fn new() -> Self {
+        Self {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            _f: PhantomData,
+        }
+    }
+
+    pub fn set(&self, f: Option<F>) {
+        let ptr = match f {
+            // SAFETY: `F` is a function pointer.
+            Some(f) => unsafe { mem::transmute_copy::<F, *mut ()>(&f) },
+            None => ptr::null_mut(),
+        };
+        self.ptr.store(ptr, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn get(&self) -> Option<F> {
+        let ptr = self.ptr.load(Ordering::Acquire);
+        if ptr.is_null() {
+            return None;
+        }
+        // SAFETY: `ptr` was created from an `F` in `set`.
+        Some(unsafe { mem::transmute_copy::<*mut (), F>(&ptr) })
+    }
+}
@@ -1,9 +1,15 @@
 use core::arch::asm;
+use core::convert::Infallible;
 
 pub type Flags = u64;
 
+pub type Error = Infallible;
+
+/// IRQ mask bit.
+const DAIF_I: u64 = 1 << 7;
+
 #[inline]
-pub fn read_disable() -> Flags {
+pub fn read_disable() -> Result<Flags, Error> {
     let daif: Flags;
     unsafe {
         asm!(
@@ -16,11 +22,11 @@ pub fn read_disable() -> Flags {
             options(preserves_flags, nostack)
         );
     }
-    daif
+    Ok(daif)
 }
 
 #[inline]
-pub fn restore(daif: Flags) {
+pub fn restore(daif: Flags) -> Result<(), Error> {
     unsafe {
         asm!(
             "msr DAIF, {}",
@@ -31,4 +37,74 @@ pub fn restore(daif: Flags) {
             options(preserves_flags, nostack)
         );
     }
+    Ok(())
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    let daif: Flags;
+    unsafe {
+        asm!(
+            "mrs {}, DAIF",
+            out(reg) daif,
+            options(nomem, preserves_flags, nostack)
+        );
+    }
+    were_enabled(daif)
+}
+
+#[inline]
+pub fn were_enabled(daif: Flags) -> bool {
+    daif & DAIF_I == 0
+}
+
+#[inline]
+pub fn enable_and_wait(daif: Flags) -> Result<(), Error> {
+    if were_enabled(daif) {
+        unsafe {
+            asm!(
+                // `wfi` wakes up on pending interrupts even if they are masked.
+                // The interrupt is taken once DAIF is restored.
+                "wfi",
+                "msr DAIF, {}",
+                in(reg) daif,
+                // Omit `nomem` to imitate a lock release.
+                // Otherwise, the compiler is free to move
+                // reads and writes through this asm block.
+                options(preserves_flags, nostack)
+            );
+        }
+    } else {
+        restore(daif)?;
+    }
+    Ok(())
+}
+
+/// Unconditionally enables interrupts.
+#[cfg(feature = "soft-disable")]
+#[inline]
+pub fn enable() {
+    unsafe {
+        asm!(
+            "msr DAIFClr, 0b0010",
+            // Omit `nomem` to imitate a lock release.
+            // Otherwise, the compiler is free to move
+            // reads and writes through this asm block.
+            options(preserves_flags, nostack)
+        );
+    }
+}
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+#[inline]
+pub fn timestamp() -> u64 {
+    let cntvct: u64;
+    unsafe {
+        asm!(
+            "mrs {}, CNTVCT_EL0",
+            out(reg) cntvct,
+            options(nomem, preserves_flags, nostack)
+        );
+    }
+    cntvct
 }
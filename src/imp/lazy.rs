@@ -0,0 +1,228 @@
+//! Lazy signal disabling.
+//!
+//! Instead of changing the signal mask, disabling only sets a thread-local flag.
+//! Signal handlers installed via [`set_handler`] check this flag.
+//! If it is set, the signal is recorded as pending and its handler is run once the flag is cleared.
+//!
+//! Signals without handlers installed through this crate are not deferred.
+
+use core::sync::atomic::{compiler_fence, AtomicBool, AtomicU64, Ordering};
+
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal};
+
+use crate::hook::Hook;
+
+/// Whether signals were enabled.
+pub type Flags = bool;
+
+pub type Error = Errno;
+
+/// The number of signals that can be deferred.
+const SIGNALS: usize = u64::BITS as usize;
+
+static HANDLERS: [Hook<fn(Signal)>; SIGNALS] = [const { Hook::new() }; SIGNALS];
+
+std::thread_local! {
+    /// Whether signals are disabled on this thread.
+    static DISABLED: AtomicBool = const { AtomicBool::new(false) };
+
+    /// Bitmap of signals that arrived on this thread while signals were disabled.
+    static PENDING: AtomicU64 = const { AtomicU64::new(0) };
+}
+
+#[inline]
+pub fn read_disable() -> Result<Flags, Error> {
+    let disabled = DISABLED.with(|disabled| {
+        let was_disabled = disabled.load(Ordering::Relaxed);
+        disabled.store(true, Ordering::Relaxed);
+        was_disabled
+    });
+    // Signal handlers run on the same thread, so a compiler fence suffices.
+    compiler_fence(Ordering::SeqCst);
+    Ok(!disabled)
+}
+
+#[inline]
+pub fn restore(flags: Flags) -> Result<(), Error> {
+    if flags {
+        enable();
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    !DISABLED.with(|disabled| disabled.load(Ordering::Relaxed))
+}
+
+#[inline]
+pub fn were_enabled(flags: Flags) -> bool {
+    flags
+}
+
+pub fn enable_and_wait(flags: Flags) -> Result<(), Error> {
+    if !flags {
+        return Ok(());
+    }
+
+    // Block signals for real to avoid missing a wakeup between checking for pending signals and waiting.
+    let mask = SigSet::all().thread_swap_mask(SigmaskHow::SIG_SETMASK)?;
+    if PENDING.with(|pending| pending.load(Ordering::Relaxed)) == 0 {
+        DISABLED.with(|disabled| disabled.store(false, Ordering::Relaxed));
+        compiler_fence(Ordering::SeqCst);
+        // `sigsuspend` atomically replaces the signal mask and waits for a signal.
+        let res = Errno::result(unsafe { libc::sigsuspend(mask.as_ref()) });
+        compiler_fence(Ordering::SeqCst);
+        DISABLED.with(|disabled| disabled.store(true, Ordering::Relaxed));
+        match res {
+            Err(Errno::EINTR) | Ok(_) => {}
+            Err(err) => {
+                enable();
+                mask.thread_set_mask()?;
+                return Err(err);
+            }
+        }
+    }
+    mask.thread_set_mask()?;
+    enable();
+    Ok(())
+}
+
+/// Clears the disabled flag and runs the handlers of pending signals.
+#[cold]
+fn enable() {
+    loop {
+        compiler_fence(Ordering::SeqCst);
+        DISABLED.with(|disabled| disabled.store(false, Ordering::Relaxed));
+        compiler_fence(Ordering::SeqCst);
+
+        // Signals arriving from here on run directly, so none can be missed.
+        let pending = PENDING.with(|pending| pending.swap(0, Ordering::Relaxed));
+        if pending == 0 {
+            return;
+        }
+
+        // Handlers expect to run with signals disabled.
+        DISABLED.with(|disabled| disabled.store(true, Ordering::Relaxed));
+        compiler_fence(Ordering::SeqCst);
+        for signo in (0..SIGNALS).filter(|signo| pending & (1 << signo) != 0) {
+            run_handler(signo as libc::c_int);
+        }
+    }
+}
+
+fn run_handler(signo: libc::c_int) {
+    let Some(handler) = HANDLERS[signo as usize].get() else {
+        return;
+    };
+    let Ok(signal) = Signal::try_from(signo) else {
+        return;
+    };
+    handler(signal);
+}
+
+extern "C" fn trampoline(signo: libc::c_int) {
+    // The handler runs with all signals blocked, see `set_handler`.
+    let errno = Errno::last_raw();
+
+    let disabled = DISABLED.with(|disabled| disabled.load(Ordering::Relaxed));
+    if disabled {
+        PENDING.with(|pending| pending.fetch_or(1 << signo, Ordering::Relaxed));
+    } else {
+        DISABLED.with(|disabled| disabled.store(true, Ordering::Relaxed));
+        compiler_fence(Ordering::SeqCst);
+        run_handler(signo);
+        compiler_fence(Ordering::SeqCst);
+        DISABLED.with(|disabled| disabled.store(false, Ordering::Relaxed));
+    }
+
+    Errno::set_raw(errno);
+}
+
+/// Installs `handler` for `signal`, deferring it while signals are disabled.
+///
+/// # Safety
+///
+/// See [`crate::unix::set_handler`].
+pub unsafe fn set_handler(signal: Signal, handler: Option<fn(Signal)>) -> Result<(), Error> {
+    let signo = signal as usize;
+    assert!(signo < SIGNALS, "signal {signal} cannot be deferred");
+
+    let sa_handler = match handler {
+        Some(_) => SigHandler::Handler(trampoline),
+        None => SigHandler::SigDfl,
+    };
+    HANDLERS[signo].set(handler);
+    let action = SigAction::new(sa_handler, SaFlags::SA_RESTART, SigSet::all());
+    unsafe { signal::sigaction(signal, &action) }?;
+    Ok(())
+}
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+#[inline]
+pub fn timestamp() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // `clock_gettime` is async-signal-safe and cannot fail for `CLOCK_MONOTONIC`.
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn lazy() {
+        static RAN: AtomicUsize = AtomicUsize::new(0);
+
+        fn handle_sighup(signal: Signal) {
+            assert_eq!(signal, Signal::SIGHUP);
+            assert!(!crate::are_enabled());
+            RAN.fetch_add(1, Ordering::Relaxed);
+        }
+
+        unsafe { crate::unix::set_handler(Signal::SIGHUP, Some(handle_sighup)) }.unwrap();
+
+        signal::raise(Signal::SIGHUP).unwrap();
+        assert_eq!(RAN.load(Ordering::Relaxed), 1);
+
+        let guard = crate::disable();
+        // The signal mask is not touched.
+        assert!(!SigSet::thread_get_mask().unwrap().contains(Signal::SIGHUP));
+        signal::raise(Signal::SIGHUP).unwrap();
+        crate::without(|| {});
+        assert_eq!(RAN.load(Ordering::Relaxed), 1);
+        drop(guard);
+        assert_eq!(RAN.load(Ordering::Relaxed), 2);
+
+        unsafe { crate::unix::set_handler(Signal::SIGHUP, None) }.unwrap();
+    }
+
+    #[test]
+    fn enable_and_wait() {
+        static RAN: AtomicUsize = AtomicUsize::new(0);
+
+        fn handle_sigquit(_signal: Signal) {
+            RAN.fetch_add(1, Ordering::Relaxed);
+        }
+
+        unsafe { crate::unix::set_handler(Signal::SIGQUIT, Some(handle_sigquit)) }.unwrap();
+
+        let guard = crate::disable();
+        signal::raise(Signal::SIGQUIT).unwrap();
+        // Returns immediately, since a signal is pending.
+        guard.enable_and_wait();
+        assert_eq!(RAN.load(Ordering::Relaxed), 1);
+
+        unsafe { crate::unix::set_handler(Signal::SIGQUIT, None) }.unwrap();
+    }
+}
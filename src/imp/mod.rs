@@ -1,18 +1,101 @@
 cfg_if::cfg_if! {
-    if #[cfg(all(unix, not(miri)))] {
+    if #[cfg(all(unix, not(miri), feature = "lazy-signals"))] {
+        mod lazy;
+        pub use self::lazy::*;
+    } else if #[cfg(all(unix, not(miri)))] {
         mod unix;
         pub use self::unix::*;
     } else if #[cfg(all(target_os = "none", target_arch = "aarch64"))] {
-        mod aarch64;
-        pub use self::aarch64::*;
+        pub mod aarch64;
+        pub use self::aarch64 as hard;
+    } else if #[cfg(all(target_os = "none", target_arch = "riscv32"))] {
+        pub mod riscv32;
+        pub use self::riscv32 as hard;
     } else if #[cfg(all(target_os = "none", target_arch = "riscv64"))] {
-        mod riscv64;
-        pub use self::riscv64::*;
+        pub mod riscv64;
+        pub use self::riscv64 as hard;
     } else if #[cfg(all(target_os = "none", target_arch = "x86_64"))] {
-        mod x86_64;
-        pub use self::x86_64::*;
+        pub mod x86_64;
+        pub use self::x86_64 as hard;
     } else {
         mod unsupported;
         pub use self::unsupported::*;
     }
 }
+
+cfg_if::cfg_if! {
+    if #[cfg(all(
+        target_os = "none",
+        feature = "soft-disable",
+        any(target_arch = "aarch64", target_arch = "riscv64", target_arch = "x86_64")
+    ))] {
+        pub mod soft;
+        pub use self::soft::*;
+    } else if #[cfg(all(test, not(target_os = "none")))] {
+        // Test the soft-disable state machine on the host with simulated hardware.
+        pub mod soft;
+    } else if #[cfg(all(
+        target_os = "none",
+        any(
+            target_arch = "aarch64",
+            target_arch = "riscv32",
+            target_arch = "riscv64",
+            target_arch = "x86_64"
+        )
+    ))] {
+        pub use self::hard::*;
+    }
+}
+
+/// Returns whether restoring `flags` leaves no interrupts masked.
+///
+/// Only Unix without `lazy-signals` can restore a partially masked state, for example in a signal handler.
+#[cfg(not(all(unix, not(miri), not(feature = "lazy-signals"))))]
+#[inline]
+pub fn were_fully_enabled(flags: Flags) -> bool {
+    were_enabled(flags)
+}
+
+/// Runs `f` with interrupts disabled without creating a [`Guard`].
+///
+/// This is used for accessing CPU-local state without being recorded as a critical section.
+///
+/// # Panics
+///
+/// Panics if interrupts could not be disabled.
+///
+/// [`Guard`]: crate::Guard
+#[cfg(any(feature = "latency-stats", feature = "unsafe-assume-single-core", test))]
+#[inline]
+pub fn without<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    match try_without(f) {
+        Ok(ret) => ret,
+        Err(err) => panic!("failed to disable interrupts: {}", crate::Error(err)),
+    }
+}
+
+/// Runs `f` with interrupts disabled without creating a [`Guard`], returning an error if interrupts could not be disabled.
+///
+/// In contrast to [`without`], this never panics, which makes it usable in destructors.
+/// If interrupts could not be disabled, `f` is not run.
+/// Errors when restoring interrupts are handled like in [`Guard`]'s destructor.
+///
+/// [`Guard`]: crate::Guard
+#[inline]
+pub fn try_without<F, R>(f: F) -> Result<R, Error>
+where
+    F: FnOnce() -> R,
+{
+    let flags = read_disable()?;
+
+    let ret = f();
+
+    if let Err(err) = restore(flags) {
+        crate::error::restore_failed(crate::Error(err));
+    }
+
+    Ok(ret)
+}
@@ -0,0 +1,110 @@
+use core::arch::asm;
+use core::convert::Infallible;
+
+pub type Flags = u8;
+
+pub type Error = Infallible;
+
+/// Machine Interrupt Enable.
+///
+/// 32-bit RISC-V targets are usually microcontrollers running in machine mode.
+const MSTATUS_MIE: usize = 1 << 3;
+
+#[inline]
+pub fn read_disable() -> Result<Flags, Error> {
+    let flags: Flags;
+    unsafe {
+        asm!(
+            // Atomic Read and Clear Immediate Bits in CSR
+            // `csrx rd, csr, rs1`
+            // Clear MIE
+            "csrrci {rd}, mstatus, 0b1000",
+            rd = out(reg) flags,
+            // Omit `nomem` to imitate a lock acquire.
+            // Otherwise, the compiler is free to move
+            // reads and writes through this asm block.
+            options(preserves_flags, nostack)
+        );
+    }
+    Ok(flags)
+}
+
+#[inline]
+pub fn restore(flags: Flags) -> Result<(), Error> {
+    unsafe {
+        asm!(
+            // Atomic Set Bits in CSR
+            "csrs mstatus, {rs1}",
+            rs1 = in(reg) flags,
+            // Omit `nomem` to imitate a lock release.
+            // Otherwise, the compiler is free to move
+            // reads and writes through this asm block.
+            options(preserves_flags, nostack)
+        );
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    let mstatus: usize;
+    unsafe {
+        asm!(
+            "csrr {rd}, mstatus",
+            rd = out(reg) mstatus,
+            options(nomem, preserves_flags, nostack)
+        );
+    }
+    mstatus & MSTATUS_MIE == MSTATUS_MIE
+}
+
+#[inline]
+pub fn were_enabled(flags: Flags) -> bool {
+    usize::from(flags) & MSTATUS_MIE == MSTATUS_MIE
+}
+
+#[inline]
+pub fn enable_and_wait(flags: Flags) -> Result<(), Error> {
+    if were_enabled(flags) {
+        unsafe {
+            asm!(
+                // `wfi` wakes up on pending interrupts even if MIE is clear.
+                // The interrupt is taken once MIE is set again.
+                "wfi",
+                "csrs mstatus, {rs1}",
+                rs1 = in(reg) flags,
+                // Omit `nomem` to imitate a lock release.
+                // Otherwise, the compiler is free to move
+                // reads and writes through this asm block.
+                options(preserves_flags, nostack)
+            );
+        }
+    } else {
+        restore(flags)?;
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+#[inline]
+pub fn timestamp() -> u64 {
+    let (mut hi, mut lo, mut hi2): (u32, u32, u32);
+    loop {
+        unsafe {
+            asm!(
+                // Read the 64-bit cycle counter in two halves.
+                "csrr {hi}, mcycleh",
+                "csrr {lo}, mcycle",
+                "csrr {hi2}, mcycleh",
+                hi = out(reg) hi,
+                lo = out(reg) lo,
+                hi2 = out(reg) hi2,
+                options(nomem, preserves_flags, nostack)
+            );
+        }
+        // Retry if the low half overflowed in between.
+        if hi == hi2 {
+            return u64::from(hi) << 32 | u64::from(lo);
+        }
+    }
+}
@@ -1,9 +1,15 @@
 use core::arch::asm;
+use core::convert::Infallible;
 
 pub type Flags = u8;
 
+pub type Error = Infallible;
+
+/// Supervisor Interrupt Enable.
+const SSTATUS_SIE: u64 = 1 << 1;
+
 #[inline]
-pub fn read_disable() -> Flags {
+pub fn read_disable() -> Result<Flags, Error> {
     let flags: Flags;
     unsafe {
         asm!(
@@ -18,11 +24,11 @@ pub fn read_disable() -> Flags {
             options(preserves_flags, nostack)
         );
     }
-    flags
+    Ok(flags)
 }
 
 #[inline]
-pub fn restore(flags: Flags) {
+pub fn restore(flags: Flags) -> Result<(), Error> {
     unsafe {
         asm!(
             // Atomic Set Bits in CSR
@@ -34,4 +40,75 @@ pub fn restore(flags: Flags) {
             options(preserves_flags, nostack)
         );
     }
+    Ok(())
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    let sstatus: u64;
+    unsafe {
+        asm!(
+            "csrr {rd}, sstatus",
+            rd = out(reg) sstatus,
+            options(nomem, preserves_flags, nostack)
+        );
+    }
+    sstatus & SSTATUS_SIE == SSTATUS_SIE
+}
+
+#[inline]
+pub fn were_enabled(flags: Flags) -> bool {
+    u64::from(flags) & SSTATUS_SIE == SSTATUS_SIE
+}
+
+#[inline]
+pub fn enable_and_wait(flags: Flags) -> Result<(), Error> {
+    if were_enabled(flags) {
+        unsafe {
+            asm!(
+                // `wfi` wakes up on pending interrupts even if SIE is clear.
+                // The interrupt is taken once SIE is set again.
+                "wfi",
+                "csrs sstatus, {rs1}",
+                rs1 = in(reg) flags,
+                // Omit `nomem` to imitate a lock release.
+                // Otherwise, the compiler is free to move
+                // reads and writes through this asm block.
+                options(preserves_flags, nostack)
+            );
+        }
+    } else {
+        restore(flags)?;
+    }
+    Ok(())
+}
+
+/// Unconditionally enables interrupts.
+#[cfg(feature = "soft-disable")]
+#[inline]
+pub fn enable() {
+    unsafe {
+        asm!(
+            // Atomic Set Bits in CSR
+            "csrsi sstatus, 0b10",
+            // Omit `nomem` to imitate a lock release.
+            // Otherwise, the compiler is free to move
+            // reads and writes through this asm block.
+            options(preserves_flags, nostack)
+        );
+    }
+}
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+#[inline]
+pub fn timestamp() -> u64 {
+    let time: u64;
+    unsafe {
+        asm!(
+            "rdtime {rd}",
+            rd = out(reg) time,
+            options(nomem, preserves_flags, nostack)
+        );
+    }
+    time
 }
@@ -0,0 +1,286 @@
+//! Soft interrupt disabling.
+//!
+//! Instead of masking interrupts in hardware, disabling only sets a CPU-local flag.
+//! Interrupt handlers call [`handler_entry`], which records the interrupt as pending if the flag is set.
+//! The kernel then returns from the interrupt with interrupts masked in hardware.
+//! Once the flag is cleared, pending interrupts are replayed via the replay hook and interrupts are unmasked again.
+
+use core::cell::Cell;
+use core::convert::Infallible;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+#[cfg(not(target_os = "none"))]
+use self::tests::hard;
+#[cfg(target_os = "none")]
+use super::hard;
+use crate::hook::Hook;
+use crate::local;
+
+/// The state to restore.
+#[derive(Clone, Copy, Debug)]
+pub struct Flags {
+    /// Whether interrupts were already soft-disabled.
+    ///
+    /// This is restored exactly, independently of the hardware state.
+    soft_disabled: bool,
+    /// Whether interrupts were enabled, that is, neither soft-disabled nor masked in hardware.
+    enabled: bool,
+}
+
+pub type Error = Infallible;
+
+static REPLAY_HOOK: Hook<fn(u8)> = Hook::new();
+
+/// CPU-local soft-disable state.
+pub struct SoftState {
+    /// Whether interrupts are soft-disabled.
+    disabled: Cell<bool>,
+    /// Whether an interrupt arrived while soft-disabled and the kernel masked interrupts in hardware.
+    hard_disabled: Cell<bool>,
+    /// Bitmap of pending interrupts.
+    pending: [Cell<u64>; 4],
+}
+
+impl SoftState {
+    pub const fn new() -> Self {
+        Self {
+            disabled: Cell::new(false),
+            hard_disabled: Cell::new(false),
+            pending: [const { Cell::new(0) }; 4],
+        }
+    }
+}
+
+#[inline]
+pub fn read_disable() -> Result<Flags, Error> {
+    let flags = local::with(|local| {
+        let soft_disabled = local.soft.disabled.replace(true);
+        Flags {
+            soft_disabled,
+            enabled: !soft_disabled && hard::are_enabled(),
+        }
+    });
+    // Interrupt handlers run on the same CPU, so a compiler fence suffices.
+    compiler_fence(Ordering::SeqCst);
+    Ok(flags)
+}
+
+#[inline]
+pub fn restore(flags: Flags) -> Result<(), Error> {
+    if flags.soft_disabled {
+        return Ok(());
+    }
+
+    if flags.enabled {
+        enable();
+    } else {
+        // Interrupts are masked in hardware, for example in an interrupt handler.
+        // Only clear the flag, since unmasking interrupts is up to whoever masked them.
+        compiler_fence(Ordering::SeqCst);
+        local::with(|local| local.soft.disabled.set(false));
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    !local::with(|local| local.soft.disabled.get()) && hard::are_enabled()
+}
+
+#[inline]
+pub fn were_enabled(flags: Flags) -> bool {
+    flags.enabled
+}
+
+#[inline]
+pub fn enable_and_wait(flags: Flags) -> Result<(), Error> {
+    if !flags.enabled {
+        return restore(flags);
+    }
+
+    // Mask interrupts in hardware to avoid missing a wakeup between checking for pending interrupts and waiting.
+    let Ok(hard_flags) = hard::read_disable();
+    let hard_disabled = local::with(|local| local.soft.hard_disabled.get());
+    if hard_disabled {
+        // The kernel keeps interrupts masked until pending interrupts are replayed.
+        enable();
+    } else {
+        compiler_fence(Ordering::SeqCst);
+        local::with(|local| local.soft.disabled.set(false));
+        let Ok(()) = hard::enable_and_wait(hard_flags);
+    }
+    Ok(())
+}
+
+#[cfg(all(
+    target_os = "none",
+    any(feature = "latency-stats", feature = "tracing")
+))]
+pub use super::hard::timestamp;
+
+/// Clears the soft-disable flag and replays pending interrupts.
+#[inline]
+fn enable() {
+    compiler_fence(Ordering::SeqCst);
+    local::with(|local| local.soft.disabled.set(false));
+    compiler_fence(Ordering::SeqCst);
+
+    // Interrupts arriving from here on are handled directly.
+    // If one arrived before, interrupts are now masked in hardware.
+    if local::with(|local| local.soft.hard_disabled.get()) {
+        replay();
+    }
+}
+
+#[cold]
+fn replay() {
+    local::with(|local| {
+        // Replayed handlers expect interrupts to be disabled.
+        local.soft.disabled.set(true);
+        for (i, pending) in local.soft.pending.iter().enumerate() {
+            while pending.get() != 0 {
+                let bit = pending.get().trailing_zeros();
+                pending.set(pending.get() & !(1 << bit));
+                let irq = (i * 64) as u8 + bit as u8;
+                if let Some(hook) = REPLAY_HOOK.get() {
+                    hook(irq);
+                }
+            }
+        }
+        local.soft.hard_disabled.set(false);
+        local.soft.disabled.set(false);
+    });
+    compiler_fence(Ordering::SeqCst);
+    hard::enable();
+}
+
+#[inline]
+pub fn handler_entry(irq: u8) -> bool {
+    local::with(|local| {
+        if !local.soft.disabled.get() {
+            return true;
+        }
+
+        let pending = &local.soft.pending[usize::from(irq / 64)];
+        pending.set(pending.get() | 1 << (irq % 64));
+        local.soft.hard_disabled.set(true);
+        false
+    })
+}
+
+pub fn set_replay_hook(hook: Option<fn(u8)>) {
+    REPLAY_HOOK.set(hook);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Simulated interrupt masking in hardware.
+    pub mod hard {
+        use core::cell::Cell;
+        use core::convert::Infallible;
+
+        std::thread_local! {
+            pub static ENABLED: Cell<bool> = const { Cell::new(true) };
+        }
+
+        pub fn read_disable() -> Result<bool, Infallible> {
+            Ok(ENABLED.replace(false))
+        }
+
+        pub fn are_enabled() -> bool {
+            ENABLED.get()
+        }
+
+        pub fn enable_and_wait(enable: bool) -> Result<(), Infallible> {
+            ENABLED.set(enable);
+            Ok(())
+        }
+
+        pub fn enable() {
+            ENABLED.set(true);
+        }
+    }
+
+    std::thread_local! {
+        static REPLAYED: Cell<Vec<u8>> = const { Cell::new(Vec::new()) };
+    }
+
+    /// Simulates the kernel's interrupt entry, returning whether the handler ran.
+    fn interrupt(irq: u8) -> bool {
+        assert!(hard::are_enabled());
+        hard::ENABLED.set(false);
+        let handled = handler_entry(irq);
+        if handled {
+            // The handler uses a nested critical section.
+            let Ok(flags) = read_disable();
+            assert!(!were_enabled(flags));
+            let Ok(()) = restore(flags);
+        }
+        // Return from the interrupt, keeping interrupts masked if the interrupt was deferred.
+        hard::ENABLED.set(handled);
+        handled
+    }
+
+    fn replayed() -> Vec<u8> {
+        REPLAYED.take()
+    }
+
+    #[test]
+    fn soft() {
+        fn replay(irq: u8) {
+            assert!(!are_enabled());
+            let mut replayed = REPLAYED.take();
+            replayed.push(irq);
+            REPLAYED.set(replayed);
+        }
+
+        set_replay_hook(Some(replay));
+
+        // Interrupts are handled directly and leave the soft state untouched.
+        assert!(interrupt(1));
+        assert!(are_enabled());
+
+        // Interrupts are deferred while soft-disabled and replayed by the outermost restore.
+        let Ok(outer) = read_disable();
+        assert!(were_enabled(outer));
+        assert!(hard::are_enabled());
+        assert!(!interrupt(130));
+        assert!(!hard::are_enabled());
+        let Ok(inner) = read_disable();
+        assert!(!were_enabled(inner));
+        let Ok(()) = restore(inner);
+        assert!(replayed().is_empty());
+        let Ok(()) = restore(outer);
+        assert_eq!(replayed(), [130]);
+        assert!(hard::are_enabled());
+        assert!(are_enabled());
+
+        // Critical sections with interrupts masked in hardware clear the soft flag again.
+        hard::ENABLED.set(false);
+        let Ok(flags) = read_disable();
+        assert!(!were_enabled(flags));
+        let Ok(()) = restore(flags);
+        hard::ENABLED.set(true);
+        assert!(are_enabled());
+        assert!(interrupt(2));
+
+        // Waiting replays pending interrupts instead of waiting.
+        let Ok(flags) = read_disable();
+        assert!(!interrupt(3));
+        let Ok(()) = enable_and_wait(flags);
+        assert_eq!(replayed(), [3]);
+        assert!(are_enabled());
+
+        let Ok(flags) = read_disable();
+        let Ok(()) = enable_and_wait(flags);
+        assert!(replayed().is_empty());
+        assert!(are_enabled());
+
+        set_replay_hook(None);
+    }
+}
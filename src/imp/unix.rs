@@ -1,17 +1,108 @@
-use nix::sys::signal::{SigSet, SigmaskHow};
+use core::cell::Cell;
 
-pub type Flags = SigSet;
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::signal::{SigSet, SigmaskHow, Signal};
+
+/// The signal mask to restore.
+///
+/// `None` if all signals were already blocked by an outer guard, so that nothing has to be restored.
+pub type Flags = Option<SigSet>;
+
+pub type Error = Errno;
+
+std::thread_local! {
+    /// Whether this crate has blocked all signals on this thread.
+    ///
+    /// This allows nested guards to skip the `sigprocmask` syscalls.
+    /// It is only set while all signals are blocked, so signal handlers never observe it.
+    static BLOCKED: Cell<bool> = const { Cell::new(false) };
+}
+
+#[inline]
+pub fn read_disable() -> Result<Flags, Error> {
+    if BLOCKED.get() {
+        return Ok(None);
+    }
+
+    let mask = SigSet::all().thread_swap_mask(SigmaskHow::SIG_SETMASK)?;
+    BLOCKED.set(true);
+    Ok(Some(mask))
+}
+
+#[inline]
+pub fn restore(flags: Flags) -> Result<(), Error> {
+    let Some(mask) = flags else {
+        return Ok(());
+    };
+
+    // Signals are still blocked, so no handler can observe this before the mask is restored.
+    BLOCKED.set(false);
+    mask.thread_set_mask()
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    if BLOCKED.get() {
+        return false;
+    }
+
+    let mask = SigSet::thread_get_mask().expect("failed to read signal mask");
+    were_enabled(Some(mask))
+}
 
 #[inline]
-pub fn read_disable() -> Flags {
-    SigSet::all()
-        .thread_swap_mask(SigmaskHow::SIG_SETMASK)
-        .unwrap()
+pub fn were_enabled(flags: Flags) -> bool {
+    let Some(mask) = flags else {
+        return false;
+    };
+
+    // SIGKILL and SIGSTOP cannot be blocked.
+    !Signal::iterator()
+        .filter(|signal| !matches!(signal, Signal::SIGKILL | Signal::SIGSTOP))
+        .all(|signal| mask.contains(signal))
+}
+
+/// Returns whether `flags` do not block any signal.
+#[inline]
+pub fn were_fully_enabled(flags: Flags) -> bool {
+    let Some(mask) = flags else {
+        return false;
+    };
+
+    !Signal::iterator().any(|signal| mask.contains(signal))
+}
+
+#[inline]
+pub fn enable_and_wait(flags: Flags) -> Result<(), Error> {
+    match flags {
+        Some(mask) if were_enabled(flags) => {
+            // Signal handlers run while waiting and have to see the actual state.
+            BLOCKED.set(false);
+            // `sigsuspend` atomically replaces the signal mask and waits for a signal.
+            let res = Errno::result(unsafe { libc::sigsuspend(mask.as_ref()) });
+            restore(flags)?;
+            match res {
+                Err(Errno::EINTR) | Ok(_) => Ok(()),
+                Err(err) => Err(err),
+            }
+        }
+        _ => restore(flags),
+    }
 }
 
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
 #[inline]
-pub fn restore(flags: Flags) {
-    flags.thread_set_mask().unwrap();
+pub fn timestamp() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // `clock_gettime` is async-signal-safe and cannot fail for `CLOCK_MONOTONIC`.
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
 }
 
 #[cfg(test)]
@@ -38,4 +129,89 @@ mod tests {
         drop(guard);
         assert!(HANDLER_RAN.load(Ordering::Relaxed));
     }
+
+    #[test]
+    fn are_enabled() {
+        use nix::sys::signal::{SigSet, Signal};
+
+        assert!(crate::are_enabled());
+
+        let guard = crate::disable();
+        assert!(!crate::are_enabled());
+        drop(guard);
+        assert!(crate::are_enabled());
+
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGUSR1);
+        let guard = crate::unix::disable_with(mask);
+        assert!(crate::are_enabled());
+        drop(guard);
+    }
+
+    #[test]
+    fn nested() {
+        let guard = crate::disable();
+        // Nested guards do not touch the signal mask.
+        let flags = super::read_disable().unwrap();
+        assert_eq!(flags, None);
+        assert!(!super::were_enabled(flags));
+        super::restore(flags).unwrap();
+        assert!(!crate::are_enabled());
+        drop(guard);
+        assert!(crate::are_enabled());
+    }
+
+    #[test]
+    fn enable_and_wait() {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        use nix::libc;
+        use nix::sys::signal::{self, SigHandler, Signal};
+
+        static HANDLER_RAN: AtomicBool = AtomicBool::new(false);
+
+        extern "C" fn handle_sigalrm(_signal: libc::c_int) {
+            HANDLER_RAN.store(true, Ordering::Relaxed);
+        }
+
+        let handler = SigHandler::Handler(handle_sigalrm);
+        unsafe { signal::signal(Signal::SIGALRM, handler) }.unwrap();
+
+        let guard = crate::disable();
+        // The signal arrives before waiting and must not be lost.
+        signal::raise(Signal::SIGALRM).unwrap();
+        assert!(!HANDLER_RAN.load(Ordering::Relaxed));
+        guard.enable_and_wait();
+        assert!(HANDLER_RAN.load(Ordering::Relaxed));
+        assert!(crate::are_enabled());
+    }
+
+    #[test]
+    fn ref_cell() {
+        use nix::libc;
+        use nix::sys::signal::{self, SigHandler, Signal};
+
+        use crate::InterruptRefCell;
+
+        std::thread_local! {
+            static CELL: InterruptRefCell<usize> = const { InterruptRefCell::new(0) };
+        }
+
+        extern "C" fn handle_sigurg(_signal: libc::c_int) {
+            // Panics if the cell is already borrowed.
+            CELL.with(|cell| *cell.borrow_mut() += 1);
+        }
+
+        let handler = SigHandler::Handler(handle_sigurg);
+        unsafe { signal::signal(Signal::SIGURG, handler) }.unwrap();
+
+        CELL.with(|cell| {
+            let mut value = cell.borrow_mut();
+            signal::raise(Signal::SIGURG).unwrap();
+            assert_eq!(*value, 0);
+            *value += 1;
+            drop(value);
+            assert_eq!(*cell.borrow(), 2);
+        });
+    }
 }
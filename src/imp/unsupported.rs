@@ -1,7 +1,36 @@
+use core::convert::Infallible;
+
 pub type Flags = ();
 
+pub type Error = Infallible;
+
+#[inline]
+pub fn read_disable() -> Result<Flags, Error> {
+    Ok(())
+}
+
+#[inline]
+pub fn restore(_flags: Flags) -> Result<(), Error> {
+    Ok(())
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    true
+}
+
+#[inline]
+pub fn were_enabled(_flags: Flags) -> bool {
+    true
+}
+
 #[inline]
-pub fn read_disable() -> Flags {}
+pub fn enable_and_wait(_flags: Flags) -> Result<(), Error> {
+    Ok(())
+}
 
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
 #[inline]
-pub fn restore(_flags: Flags) {}
+pub fn timestamp() -> u64 {
+    0
+}
@@ -1,9 +1,14 @@
 use core::arch::asm;
+use core::convert::Infallible;
 
 pub type Flags = bool;
 
+pub type Error = Infallible;
+
+const INTERRUPT_FLAG: u64 = 1 << 9;
+
 #[inline]
-pub fn read_disable() -> Flags {
+pub fn read_disable() -> Result<Flags, Error> {
     let rflags: u64;
 
     unsafe {
@@ -19,22 +24,86 @@ pub fn read_disable() -> Flags {
         );
     }
 
-    const INTERRUPT_FLAG: u64 = 1 << 9;
+    Ok((rflags & INTERRUPT_FLAG) == INTERRUPT_FLAG)
+}
+
+// The soft backend uses neither `restore` nor `were_enabled`.
+// Unlike on other architectures, `are_enabled` and `enable_and_wait` do not call them either.
+#[cfg_attr(feature = "soft-disable", allow(dead_code))]
+#[inline]
+pub fn restore(enable: Flags) -> Result<(), Error> {
+    if enable {
+        unsafe {
+            asm!(
+                "sti",
+                // Omit `nomem` to imitate a lock acquire.
+                // Otherwise, the compiler is free to move
+                // reads and writes through this asm block.
+                options(preserves_flags)
+            );
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn are_enabled() -> bool {
+    let rflags: u64;
+
+    unsafe {
+        asm!(
+            "pushfq",
+            "pop {}",
+            out(reg) rflags,
+            options(nomem, preserves_flags)
+        );
+    }
 
     (rflags & INTERRUPT_FLAG) == INTERRUPT_FLAG
 }
 
+#[cfg_attr(feature = "soft-disable", allow(dead_code))]
 #[inline]
-pub fn restore(enable: Flags) {
+pub fn were_enabled(enable: Flags) -> bool {
+    enable
+}
+
+#[inline]
+pub fn enable_and_wait(enable: Flags) -> Result<(), Error> {
     if enable {
         unsafe {
             asm!(
+                // `sti` enables interrupts only after the next instruction.
+                // Thus, no interrupt can arrive before `hlt`.
                 "sti",
-                // Omit `nomem` to imitate a lock acquire.
+                "hlt",
+                // Omit `nomem` to imitate a lock release.
                 // Otherwise, the compiler is free to move
                 // reads and writes through this asm block.
                 options(preserves_flags)
             );
         }
     }
+    Ok(())
+}
+
+/// Unconditionally enables interrupts.
+#[cfg(feature = "soft-disable")]
+#[inline]
+pub fn enable() {
+    unsafe {
+        asm!(
+            "sti",
+            // Omit `nomem` to imitate a lock release.
+            // Otherwise, the compiler is free to move
+            // reads and writes through this asm block.
+            options(preserves_flags)
+        );
+    }
+}
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+#[inline]
+pub fn timestamp() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
 }
@@ -10,6 +10,8 @@
 //!
 //!     - AArch64 (`arch = aarch64`)
 //!
+//!     - 32-bit RISC-V in machine mode (`arch = riscv32`)
+//!
 //!     - 64-bit RISC-V (`arch = riscv64`)
 //!
 //!     - x86-64 (`arch = x86_64`)
@@ -18,6 +20,8 @@
 //!
 //!     Disables signals.
 //!
+//!     To block only some signals, see the [`unix`] module.
+//!
 //! On targets with non-unix operating systems (not `cfg!(unix)`), this crate does nothing.
 //!
 //! # Caveats
@@ -52,19 +56,147 @@
 //! // interrupts are restored to the previous state
 //! ```
 //!
-//! # Related Crates
+//! Use `Mutex` (`lock_api` feature) to share data with interrupt handlers or signal handlers:
+//!
+//! ```
+//! # #[cfg(feature = "lock_api")]
+//! # {
+//! static DATA: interrupts::Mutex<u32> = interrupts::Mutex::new(0);
+//!
+//! // interrupts may or may not be enabled
+//! *DATA.lock() += 1;
+//! // interrupts are restored to the previous state
+//! # }
+//! ```
+//!
+//! `Mutex`, [`Notify`], [`OnceCell`], and [`Lazy`] require atomic compare-and-swap and are not available on targets without it, such as `riscv32imc-unknown-none-elf`.
+//! On such single-core targets, the `unsafe-assume-single-core` feature provides emulated atomic types in the `atomic` module.
+//!
+//! Use [`InterruptRefCell`] to share data with interrupt handlers or signal handlers on the same CPU or thread:
+//!
+//! ```
+//! let cell = interrupts::InterruptRefCell::new(0);
+//!
+//! // interrupts may or may not be enabled
+//! *cell.borrow_mut() += 1;
+//! // interrupts are restored to the previous state
+//! ```
+//!
+//! # Cargo Features
+//!
+//! - `critical-section`: Implement [`critical-section`] using this crate.
+//!
+//!   On bare-metal targets, this only disables interrupts on the current CPU and is thus only sound on single-core systems.
+//!   On Unix, this additionally takes a global lock.
+//!
+//! - `debug-guards`: Detect [`Guard`]s that are dropped out of order.
+//!
+//! - `lazy-signals`: On Unix, disable signals lazily using a thread-local flag instead of changing the signal mask.
+//!
+//!   This avoids all syscalls when disabling and enabling signals.
+//!   Only signals with handlers installed via `unix::set_handler` are deferred.
+//!   Their handlers are run once the outermost [`Guard`] is dropped.
+//!   Other signals are not blocked at all.
+//!
+//! - `latency-stats`: Record how long interrupts are disabled (see `stats`).
+//!
+//! - `lock_api`: Provide `Mutex` and `RawMutex` for use with [`lock_api`].
+//!
+//! - `soft-disable`: On bare-metal targets, disable interrupts lazily using a CPU-local flag instead of masking them in hardware.
 //!
-//! - [interrupt-ref-cell] (A `RefCell` for sharing data with interrupt handlers or signal handlers on the same thread.)
-//! - [interrupt-mutex] (A mutex for sharing data with interrupt handlers or signal handlers.)
+//!   Interrupt handlers have to call `soft::handler_entry` to defer interrupts that arrive while interrupts are disabled.
+//!   Deferred interrupts are replayed once the outermost [`Guard`] is dropped.
+//!   See the `soft` module for details.
+//!   32-bit RISC-V is not supported and always masks interrupts in hardware, even with this feature.
 //!
-//! [interrupt-ref-cell]: https://crates.io/crates/interrupt-ref-cell
-//! [interrupt-mutex]: https://crates.io/crates/interrupt-mutex
+//! - `track-caller`: Record the caller that disabled interrupts.
+//!
+//!   Together with `latency-stats`, this records the call sites that disabled interrupts for the longest time.
+//!   This adds [`#[track_caller]`][track_caller] to [`disable`], [`without`], and similar functions.
+//!   `lock_api` does not propagate `#[track_caller]`, so all critical sections of `Mutex` are attributed to a single location in this crate.
+//!   The same holds for [`Notify`] and [`OnceCell`], which disable interrupts internally.
+//!
+//! - `tracing`: Emit a [`tracing`] event for each critical section.
+//!
+//!   The event is emitted after interrupts have been restored and includes the duration and the caller.
+//!   The `duration` field is measured in raw ticks of the platform's timestamp counter on bare-metal targets (TSC on x86-64, `CNTVCT_EL0` on AArch64, `mcycle` on 32-bit RISC-V, `time` on 64-bit RISC-V) and in nanoseconds on Unix.
+//!   [`tracing`] requires atomic compare-and-swap and is not available on targets without it.
+//!   No events are emitted from Unix signal handlers that run with signals partially blocked, since subscribers may allocate or take locks.
+//!   With `lazy-signals`, this relies on the handlers being installed via `unix::set_handler`.
+//!   This implies `track-caller`.
+//!
+//! - `unsafe-assume-single-core`: Provide atomic types emulated by disabling interrupts (see `atomic`).
+//!
+//!   This is only sound on single-core systems and fails to compile on hosted and unsupported targets.
+//!
+//! [track_caller]: https://doc.rust-lang.org/reference/attributes/codegen.html#the-track_caller-attribute
+//! [`tracing`]: https://crates.io/crates/tracing
+//! [`lock_api`]: https://crates.io/crates/lock_api
+//!
+//! [`critical-section`]: https://crates.io/crates/critical-section
 
 #![cfg_attr(target_os = "none", no_std)]
 
+pub mod alloc;
+#[cfg(any(feature = "unsafe-assume-single-core", test))]
+pub mod atomic;
+#[cfg(feature = "critical-section")]
+mod critical_section;
+#[cfg(feature = "debug-guards")]
+mod debug_guards;
+mod deferred;
+pub mod disabled;
+mod error;
+pub mod future;
+mod hook;
 mod imp;
+mod local;
+#[cfg(target_has_atomic = "8")]
+mod mutex;
+#[cfg(target_has_atomic = "8")]
+mod notify;
+#[cfg(target_has_atomic = "ptr")]
+mod once_cell;
+pub mod preempt;
+mod queue;
+mod ref_cell;
+#[cfg(all(
+    target_os = "none",
+    feature = "soft-disable",
+    any(
+        target_arch = "aarch64",
+        target_arch = "riscv64",
+        target_arch = "x86_64"
+    )
+))]
+pub mod soft;
+#[cfg(feature = "latency-stats")]
+pub mod stats;
+mod timing;
+#[cfg(feature = "tracing")]
+mod tracing;
+mod transition;
+#[cfg(all(unix, not(miri)))]
+pub mod unix;
 
 use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+
+#[cfg(feature = "debug-guards")]
+pub use self::debug_guards::{set_drop_order_hook, DropOrderError};
+pub use self::deferred::{defer, run_deferred, DeferError};
+pub use self::error::{set_restore_error_hook, Error};
+#[cfg(target_os = "none")]
+pub use self::local::{set_cpu_local, CpuLocal};
+#[cfg(all(feature = "lock_api", target_has_atomic = "8"))]
+pub use self::mutex::{MappedMutexGuard, Mutex, MutexGuard, RawMutex};
+#[cfg(target_has_atomic = "8")]
+pub use self::notify::{Notify, Wait};
+#[cfg(target_has_atomic = "ptr")]
+pub use self::once_cell::{Lazy, OnceCell, ReentrantInitError};
+pub use self::queue::{Consumer, Producer, Queue};
+pub use self::ref_cell::{InterruptRef, InterruptRefCell, InterruptRefMut};
+pub use self::transition::{set_disable_hook, set_enable_hook};
 
 /// Temporarily disable interrupts.
 ///
@@ -79,14 +211,127 @@ use core::marker::PhantomData;
 /// drop(guard);
 /// // interrupts are restored to the previous state
 /// ```
+///
+/// # Panics
+///
+/// Panics if interrupts could not be disabled.
+/// This can only happen on Unix.
+/// Use [`try_disable`] to handle this error instead.
 #[inline]
+#[cfg_attr(feature = "track-caller", track_caller)]
 pub fn disable() -> Guard {
-    Guard {
-        flags: imp::read_disable(),
-        _not_send: PhantomData,
+    match try_disable() {
+        Ok(guard) => guard,
+        Err(err) => panic!("failed to disable interrupts: {err}"),
     }
 }
 
+/// Temporarily disable interrupts, returning an error on failure.
+///
+/// Interrupts are enabled once the returned [`Guard`] is dropped.
+///
+/// # Errors
+///
+/// On Unix, this returns an error if the signal mask could not be changed.
+/// On bare-metal targets, this never returns an error.
+///
+/// # Examples
+///
+/// ```
+/// // interrupts may or may not be enabled
+/// let guard = interrupts::try_disable()?;
+/// // interrupts are disabled
+/// drop(guard);
+/// // interrupts are restored to the previous state
+/// # Ok::<(), interrupts::Error>(())
+/// ```
+#[inline]
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn try_disable() -> Result<Guard, Error> {
+    let flags = imp::read_disable().map_err(Error)?;
+    Ok(Guard::new(flags))
+}
+
+/// Returns whether interrupts are currently enabled.
+///
+/// | Platform    | Interrupts are enabled if    |
+/// | ----------- | ---------------------------- |
+/// | AArch64     | `DAIF.I` is clear            |
+/// | RISC-V 32   | `mstatus.MIE` is set         |
+/// | RISC-V 64   | `sstatus.SIE` is set         |
+/// | x86-64      | `RFLAGS.IF` is set           |
+/// | Unix        | not all signals are blocked  |
+/// | unsupported | always                       |
+///
+/// On Unix, `SIGKILL` and `SIGSTOP` are not considered, since they cannot be blocked.
+/// Blocking only some signals, such as with [`unix::disable_with`], does not count as disabling interrupts.
+///
+/// # Panics
+///
+/// On Unix, panics if the signal mask could not be read.
+///
+/// # Examples
+///
+/// ```
+/// let guard = interrupts::disable();
+/// assert!(!interrupts::are_enabled());
+/// drop(guard);
+/// ```
+#[inline]
+pub fn are_enabled() -> bool {
+    imp::are_enabled()
+}
+
+/// Asserts that interrupts are disabled.
+///
+/// This is useful in functions that must only be called inside a critical section.
+/// See [`are_enabled`] for what counts as disabled.
+///
+/// Like [`assert!`], this macro accepts an optional custom panic message.
+///
+/// On unsupported targets, where interrupts are never disabled, this always panics.
+///
+/// # Examples
+///
+/// ```
+/// fn requires_disabled() {
+///     interrupts::assert_disabled!();
+/// }
+///
+/// interrupts::without(|| requires_disabled());
+/// ```
+#[macro_export]
+macro_rules! assert_disabled {
+    () => {
+        ::core::assert!(!$crate::are_enabled(), "interrupts are enabled")
+    };
+    ($($arg:tt)+) => {
+        ::core::assert!(!$crate::are_enabled(), $($arg)+)
+    };
+}
+
+/// Asserts that interrupts are disabled in debug builds.
+///
+/// Like [`assert_disabled!`], but only checked if `debug_assertions` are enabled.
+///
+/// # Examples
+///
+/// ```
+/// fn requires_disabled() {
+///     interrupts::debug_assert_disabled!("must be called with interrupts disabled");
+/// }
+///
+/// interrupts::without(|| requires_disabled());
+/// ```
+#[macro_export]
+macro_rules! debug_assert_disabled {
+    ($($arg:tt)*) => {
+        if ::core::cfg!(debug_assertions) {
+            $crate::assert_disabled!($($arg)*);
+        }
+    };
+}
+
 /// An interrupt guard.
 ///
 /// Created using [`disable`].
@@ -106,6 +351,18 @@ pub fn disable() -> Guard {
 ///
 /// [drop scope]: https://doc.rust-lang.org/reference/destructors.html#drop-scopes
 ///
+/// Nested guards created with [`Guard::nest`] borrow their parent and cannot be dropped in the wrong order.
+/// Alternatively, nested code can take a [`disabled::Token`] instead of creating a fresh guard.
+///
+/// With the `debug-guards` feature, dropping guards out of order panics.
+/// See `set_drop_order_hook` for reporting the error differently.
+///
+/// # Errors When Restoring
+///
+/// On Unix, restoring the signal mask may fail.
+/// Since errors cannot be returned from `drop`, this aborts the process by default.
+/// See [`set_restore_error_hook`] for reporting the error instead.
+///
 /// # Examples
 ///
 /// ```
@@ -119,6 +376,8 @@ pub fn disable() -> Guard {
 /// Dropping guards in the wrong order (don't do this):
 ///
 /// ```
+/// # #[cfg(feature = "debug-guards")]
+/// # interrupts::set_drop_order_hook(Some(|_| {}));
 /// // Interrupts are enabled
 /// let a = interrupts::disable();
 /// // Interrupts are disabled
@@ -129,6 +388,8 @@ pub fn disable() -> Guard {
 /// ```
 pub struct Guard {
     flags: imp::Flags,
+    #[cfg(feature = "debug-guards")]
+    depth: usize,
     /// Interrupts are per hardware thread.
     ///
     /// Making Guard `!Send` avoids disabling interrupts on one hardware thread and restoring on another.
@@ -136,6 +397,183 @@ pub struct Guard {
 }
 
 impl Guard {
+    /// Creates a guard after interrupts have been disabled.
+    #[inline]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    fn new(flags: imp::Flags) -> Self {
+        if imp::were_enabled(flags) {
+            timing::start();
+            transition::disabled();
+        }
+
+        Self {
+            flags,
+            #[cfg(feature = "debug-guards")]
+            depth: debug_guards::push(),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Prepares restoring the interrupt state.
+    ///
+    /// This runs while interrupts are still disabled.
+    #[inline]
+    fn before_restore(&self) -> Restore {
+        #[cfg(feature = "debug-guards")]
+        let drop_order = debug_guards::pop(self.depth);
+
+        let section = if imp::were_enabled(self.flags) {
+            transition::enabling();
+            let section = timing::stop();
+
+            #[cfg(feature = "latency-stats")]
+            stats::record(&section);
+
+            Some(section)
+        } else {
+            None
+        };
+
+        Restore {
+            section,
+            fully_enabled: imp::were_fully_enabled(self.flags),
+            #[cfg(feature = "debug-guards")]
+            drop_order,
+        }
+    }
+
+    /// Finishes restoring the interrupt state.
+    ///
+    /// This runs after interrupts have been restored.
+    #[inline]
+    fn after_restore(restore: Restore) {
+        // Signal handlers usually run with only their own signal blocked, so their guards are outermost.
+        // Subscribers, deferred work, and the scheduler must not run in such handlers, since they may allocate or take locks.
+        if let Some(section) = restore.section.filter(|_| restore.fully_enabled) {
+            #[cfg(feature = "tracing")]
+            tracing::section(&section);
+
+            #[cfg(not(feature = "tracing"))]
+            let _ = section;
+
+            deferred::run_deferred();
+            preempt::reschedule_if_needed();
+        }
+
+        #[cfg(feature = "debug-guards")]
+        if let Some(err) = restore.drop_order {
+            debug_guards::violated(err);
+        }
+    }
+
+    /// Returns a token proving that interrupts are disabled while this guard is held.
+    ///
+    /// See [`disabled`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interrupts::disabled::Token;
+    ///
+    /// fn requires_disabled(_token: Token<'_>) {
+    ///     // interrupts are disabled
+    /// }
+    ///
+    /// let guard = interrupts::disable();
+    /// requires_disabled(guard.token());
+    /// drop(guard);
+    /// ```
+    #[inline]
+    pub fn token(&self) -> disabled::Token<'_> {
+        // SAFETY: Interrupts are disabled while this guard is held.
+        unsafe { disabled::Token::new_unchecked() }
+    }
+
+    /// Creates a nested guard that cannot outlive this guard.
+    ///
+    /// Like [`disable`], this disables interrupts again.
+    /// In contrast to [`disable`], the returned guard borrows this guard.
+    /// This turns [dropping guards in the wrong order](Self#caveats-drop-order) into a compile error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if interrupts could not be disabled, like [`disable`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let guard = interrupts::disable();
+    /// let nested = guard.nest();
+    /// // interrupts are disabled
+    /// drop(nested);
+    /// // interrupts are still disabled
+    /// drop(guard);
+    /// ```
+    ///
+    /// Dropping guards in the wrong order does not compile:
+    ///
+    /// ```compile_fail
+    /// let guard = interrupts::disable();
+    /// let nested = guard.nest();
+    /// drop(guard);
+    /// drop(nested);
+    /// ```
+    #[inline]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    pub fn nest(&self) -> NestedGuard<'_> {
+        NestedGuard {
+            guard: disable(),
+            _parent: PhantomData,
+        }
+    }
+
+    /// Atomically enable interrupts and wait for the next interrupt.
+    ///
+    /// This consumes the guard, restores the previous interrupt state, and waits for an interrupt in a single step.
+    /// In contrast to dropping the guard and then waiting, an interrupt that arrives in between cannot be missed.
+    /// Once the interrupt has been handled, this function returns.
+    ///
+    /// | Platform    | Implementation                                |
+    /// | ----------- | --------------------------------------------- |
+    /// | AArch64     | `wfi` with masked `DAIF`, then restore        |
+    /// | RISC-V 32   | `wfi` with clear `mstatus.MIE`, then restore  |
+    /// | RISC-V 64   | `wfi` with clear `sstatus.SIE`, then restore  |
+    /// | x86-64      | `sti; hlt`                                    |
+    /// | Unix        | `sigsuspend` with the previous mask           |
+    /// | unsupported | returns immediately                           |
+    ///
+    /// If interrupts were already disabled when this guard was created, this does not wait and only restores the previous state, since no interrupt could be handled.
+    ///
+    /// Errors are handled like when dropping the guard.
+    ///
+    /// # Examples
+    ///
+    /// An idle loop:
+    ///
+    /// ```no_run
+    /// # fn has_work() -> bool { false }
+    /// # fn do_work() {}
+    /// loop {
+    ///     let guard = interrupts::disable();
+    ///     if has_work() {
+    ///         drop(guard);
+    ///         do_work();
+    ///     } else {
+    ///         // An interrupt handler might create new work.
+    ///         guard.enable_and_wait();
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn enable_and_wait(self) {
+        let this = ManuallyDrop::new(self);
+        let restore = this.before_restore();
+        if let Err(err) = imp::enable_and_wait(this.flags) {
+            error::restore_failed(Error(err));
+        }
+        Self::after_restore(restore);
+    }
+
     /// ```compile_fail
     /// fn send<T: Send>(_: T) {}
     ///
@@ -147,8 +585,71 @@ impl Guard {
 impl Drop for Guard {
     #[inline]
     fn drop(&mut self) {
-        #[allow(clippy::unit_arg)]
-        imp::restore(self.flags);
+        let restore = self.before_restore();
+        if let Err(err) = imp::restore(self.flags) {
+            error::restore_failed(Error(err));
+        }
+        Self::after_restore(restore);
+    }
+}
+
+/// State carried from [`Guard::before_restore`] to [`Guard::after_restore`].
+struct Restore {
+    /// The finished critical section if the guard enabled interrupts.
+    section: Option<timing::Section>,
+    /// Whether no interrupts remain masked after restoring, which is not the case in Unix signal handlers.
+    fully_enabled: bool,
+    /// A drop order violation to report once interrupts have been restored.
+    #[cfg(feature = "debug-guards")]
+    drop_order: Option<DropOrderError>,
+}
+
+/// A critical section token.
+///
+/// Proves that interrupts are disabled for `'a`.
+/// This is an alias for [`disabled::Token`].
+///
+/// Created using [`without_token`] or [`Guard::token`].
+///
+/// # Examples
+///
+/// ```
+/// use interrupts::CriticalSection;
+///
+/// fn requires_disabled(_cs: &CriticalSection<'_>) {
+///     // interrupts are disabled
+/// }
+///
+/// interrupts::without_token(|cs| requires_disabled(&cs));
+/// ```
+pub type CriticalSection<'a> = disabled::Token<'a>;
+
+/// A nested interrupt guard.
+///
+/// Created using [`Guard::nest`] or [`NestedGuard::nest`].
+///
+/// This behaves like [`Guard`] but cannot outlive the guard it was created from.
+pub struct NestedGuard<'a> {
+    guard: Guard,
+    _parent: PhantomData<&'a Guard>,
+}
+
+impl NestedGuard<'_> {
+    /// Returns a token proving that interrupts are disabled while this guard is held.
+    ///
+    /// See [`Guard::token`].
+    #[inline]
+    pub fn token(&self) -> disabled::Token<'_> {
+        self.guard.token()
+    }
+
+    /// Creates a nested guard that cannot outlive this guard.
+    ///
+    /// See [`Guard::nest`].
+    #[inline]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    pub fn nest(&self) -> NestedGuard<'_> {
+        self.guard.nest()
     }
 }
 
@@ -186,6 +687,7 @@ impl Drop for Guard {
 /// ```
 // Docs adapted from `x86_64::instructions::interrupts::without_interrupts`.
 #[inline]
+#[cfg_attr(feature = "track-caller", track_caller)]
 pub fn without<F, R>(f: F) -> R
 where
     F: FnOnce() -> R,
@@ -198,3 +700,44 @@ where
 
     ret
 }
+
+/// Run a closure with disabled interrupts, passing a [`CriticalSection`] token.
+///
+/// Like [`without`], but the closure receives proof that interrupts are disabled.
+///
+/// # Examples
+///
+/// ```
+/// use interrupts::CriticalSection;
+///
+/// fn requires_disabled(_cs: &CriticalSection<'_>) {
+///     // interrupts are disabled
+/// }
+///
+/// // interrupts may or may not be enabled
+/// interrupts::without_token(|cs| {
+///     // interrupts are disabled
+///     requires_disabled(&cs);
+/// });
+/// // interrupts are restored to the previous state
+/// ```
+///
+/// The token cannot escape the closure:
+///
+/// ```compile_fail
+/// let cs = interrupts::without_token(|cs| cs);
+/// ```
+#[inline]
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub fn without_token<F, R>(f: F) -> R
+where
+    F: FnOnce(CriticalSection<'_>) -> R,
+{
+    let guard = disable();
+
+    let ret = f(guard.token());
+
+    drop(guard);
+
+    ret
+}
@@ -0,0 +1,197 @@
+//! CPU-local state.
+//!
+//! Interrupts are disabled per CPU (or per thread on Unix), so is the state that this crate tracks.
+//! On hosted targets, this state lives in a thread-local variable.
+//! On bare-metal targets, kernels have to provide the state for each CPU via [`set_cpu_local`] unless the `unsafe-assume-single-core` feature is enabled.
+
+#[cfg(feature = "debug-guards")]
+use core::cell::Cell;
+
+/// CPU-local state of this crate.
+///
+/// On bare-metal targets with more than one CPU, each CPU needs its own instance.
+/// See [`set_cpu_local`] for details.
+pub struct CpuLocal {
+    /// Makes sure that each instance has a distinct address, which identifies the CPU.
+    _id: u8,
+    pub(crate) deferred: crate::deferred::Queue,
+    pub(crate) preempt: crate::preempt::State,
+    #[cfg(any(
+        all(
+            target_os = "none",
+            feature = "soft-disable",
+            any(
+                target_arch = "aarch64",
+                target_arch = "riscv64",
+                target_arch = "x86_64"
+            )
+        ),
+        all(test, not(target_os = "none"))
+    ))]
+    pub(crate) soft: crate::imp::soft::SoftState,
+    #[cfg(feature = "debug-guards")]
+    pub(crate) depth: Cell<usize>,
+    #[cfg(any(feature = "latency-stats", feature = "tracing"))]
+    pub(crate) timing: crate::timing::Timing,
+    #[cfg(feature = "latency-stats")]
+    pub(crate) stats: crate::stats::Stats,
+}
+
+// SAFETY: Each instance is only accessed by its own CPU.
+unsafe impl Sync for CpuLocal {}
+
+impl CpuLocal {
+    /// Creates new CPU-local state.
+    pub const fn new() -> Self {
+        Self {
+            _id: 0,
+            deferred: crate::deferred::Queue::new(),
+            preempt: crate::preempt::State::new(),
+            #[cfg(any(
+                all(
+                    target_os = "none",
+                    feature = "soft-disable",
+                    any(
+                        target_arch = "aarch64",
+                        target_arch = "riscv64",
+                        target_arch = "x86_64"
+                    )
+                ),
+                all(test, not(target_os = "none"))
+            ))]
+            soft: crate::imp::soft::SoftState::new(),
+            #[cfg(feature = "debug-guards")]
+            depth: Cell::new(0),
+            #[cfg(any(feature = "latency-stats", feature = "tracing"))]
+            timing: crate::timing::Timing::new(),
+            #[cfg(feature = "latency-stats")]
+            stats: crate::stats::Stats::new(),
+        }
+    }
+}
+
+impl Default for CpuLocal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "none")] {
+        use crate::hook::Hook;
+
+        static CPU_LOCAL: Hook<fn() -> &'static CpuLocal> = Hook::new();
+
+        static DEFAULT: CpuLocal = CpuLocal::new();
+
+        /// Set the function that returns the current CPU's [`CpuLocal`] state.
+        ///
+        /// Until this is called, [`defer`] returns an error, and [`preempt::disable`] and [`request_reschedule`] panic, since their state cannot be shared between CPUs.
+        /// With the `unsafe-assume-single-core` feature, they use a single instance shared by all CPUs instead.
+        /// Other state, such as that of the `debug-guards` and `latency-stats` features, always falls back to the shared instance, which is only correct on systems with a single CPU.
+        /// Kernels supporting multiple CPUs should embed a [`CpuLocal`] in their per-CPU data and register an accessor for it.
+        ///
+        /// [`defer`]: crate::defer
+        /// [`preempt::disable`]: crate::preempt::disable
+        /// [`request_reschedule`]: crate::preempt::request_reschedule
+        ///
+        /// # Safety
+        ///
+        /// `f` must return a distinct instance for each CPU and always the same instance on the same CPU.
+        /// `f` must be callable from any context, including interrupt handlers and with interrupts disabled.
+        /// This function must be called before any state is accessed, usually before interrupts are enabled for the first time.
+        ///
+        /// # Examples
+        ///
+        /// ```ignore
+        /// static CPU_LOCALS: [interrupts::CpuLocal; 4] = [const { interrupts::CpuLocal::new() }; 4];
+        ///
+        /// fn cpu_local() -> &'static interrupts::CpuLocal {
+        ///     &CPU_LOCALS[core_id()]
+        /// }
+        ///
+        /// unsafe { interrupts::set_cpu_local(cpu_local) };
+        /// ```
+        pub unsafe fn set_cpu_local(f: fn() -> &'static CpuLocal) {
+            CPU_LOCAL.set(Some(f));
+        }
+
+        /// Runs `f` with the current CPU's state, falling back to the shared instance.
+        ///
+        /// Only features that already assume a single CPU without [`set_cpu_local`] use this.
+        #[cfg_attr(
+            not(any(
+                feature = "debug-guards",
+                feature = "latency-stats",
+                feature = "tracing",
+                all(
+                    feature = "soft-disable",
+                    any(
+                        target_arch = "aarch64",
+                        target_arch = "riscv64",
+                        target_arch = "x86_64"
+                    )
+                )
+            )),
+            allow(dead_code)
+        )]
+        #[inline]
+        pub(crate) fn with<F, R>(f: F) -> R
+        where
+            F: FnOnce(&CpuLocal) -> R,
+        {
+            let local = match CPU_LOCAL.get() {
+                Some(cpu_local) => cpu_local(),
+                None => &DEFAULT,
+            };
+            f(local)
+        }
+
+        /// Like [`with`], but returns `None` instead of falling back to the shared instance.
+        ///
+        /// Use this for state that safe code writes to, since the shared instance would race between CPUs.
+        #[inline]
+        pub(crate) fn try_with<F, R>(f: F) -> Option<R>
+        where
+            F: FnOnce(&CpuLocal) -> R,
+        {
+            match CPU_LOCAL.get() {
+                Some(cpu_local) => Some(f(cpu_local())),
+                #[cfg(feature = "unsafe-assume-single-core")]
+                None => Some(f(&DEFAULT)),
+                #[cfg(not(feature = "unsafe-assume-single-core"))]
+                None => None,
+            }
+        }
+    } else {
+        std::thread_local! {
+            static LOCAL: CpuLocal = const { CpuLocal::new() };
+        }
+
+        #[inline]
+        pub(crate) fn with<F, R>(f: F) -> R
+        where
+            F: FnOnce(&CpuLocal) -> R,
+        {
+            LOCAL.with(f)
+        }
+
+        #[inline]
+        pub(crate) fn try_with<F, R>(f: F) -> Option<R>
+        where
+            F: FnOnce(&CpuLocal) -> R,
+        {
+            Some(with(f))
+        }
+    }
+}
+
+/// Returns a non-zero identifier of the current CPU.
+///
+/// On Unix, this identifies the current thread.
+/// Returns `None` if the CPU cannot be told apart from others, see [`try_with`].
+#[cfg(target_has_atomic = "ptr")]
+#[inline]
+pub(crate) fn id() -> Option<usize> {
+    try_with(|local| local as *const CpuLocal as usize)
+}
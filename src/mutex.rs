@@ -0,0 +1,188 @@
+use core::cell::UnsafeCell;
+use core::hint;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::Guard;
+
+/// A raw spinlock that disables interrupts while locked.
+///
+/// Locking first disables interrupts and then spins until the lock is acquired.
+/// Unlocking first releases the lock and then restores interrupts.
+/// This corresponds to Linux's `spin_lock_irqsave` and `spin_unlock_irqrestore`.
+///
+/// This type is meant to be used with [`lock_api`] (`lock_api` feature).
+/// See [`Mutex`] for the corresponding mutex type.
+pub struct RawMutex {
+    lock: AtomicBool,
+    guard: UnsafeCell<MaybeUninit<Guard>>,
+}
+
+// SAFETY: `guard` is only accessed by the lock holder.
+unsafe impl Send for RawMutex {}
+unsafe impl Sync for RawMutex {}
+
+impl RawMutex {
+    /// Creates an unlocked mutex.
+    pub(crate) const fn new() -> Self {
+        Self {
+            lock: AtomicBool::new(false),
+            guard: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    #[inline]
+    fn try_lock_weak(&self) -> bool {
+        self.lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Disables interrupts and acquires the lock.
+    #[inline]
+    pub(crate) fn acquire(&self) {
+        let guard = crate::disable();
+
+        while !self.try_lock_weak() {
+            while self.lock.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+
+        unsafe {
+            (*self.guard.get()).write(guard);
+        }
+    }
+
+    /// Releases the lock and restores interrupts.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held by the caller.
+    #[inline]
+    pub(crate) unsafe fn release(&self) {
+        let guard = unsafe { (*self.guard.get()).assume_init_read() };
+        self.lock.store(false, Ordering::Release);
+        drop(guard);
+    }
+}
+
+#[cfg(feature = "lock_api")]
+unsafe impl lock_api::RawMutex for RawMutex {
+    const INIT: Self = Self::new();
+
+    type GuardMarker = lock_api::GuardNoSend;
+
+    #[inline]
+    fn lock(&self) {
+        self.acquire();
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        let guard = crate::disable();
+
+        let ok = self
+            .lock
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok();
+
+        if ok {
+            unsafe {
+                (*self.guard.get()).write(guard);
+            }
+        }
+
+        ok
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        unsafe { self.release() }
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        self.lock.load(Ordering::Relaxed)
+    }
+}
+
+/// A mutual exclusion primitive that disables interrupts while locked.
+///
+/// This mutex is useful for sharing data with interrupt handlers or signal handlers.
+/// Interrupts are disabled before spinning on the lock and restored after releasing it.
+/// Thus, an interrupt handler on the same CPU cannot deadlock by trying to lock a mutex that is held by the code it interrupted.
+///
+/// Locking a mutex recursively deadlocks.
+///
+/// Mutexes have to be unlocked in the reverse order of locking.
+/// Each guard restores the interrupt state from when it was locked, so unlocking hand-over-hand (lock A, lock B, unlock A) enables interrupts while B is still held.
+/// An interrupt handler on the same CPU that then locks B deadlocks.
+/// With `debug-guards`, such an out-of-order unlock is reported like any other out-of-order [`Guard`] drop.
+///
+/// [`Guard`]: crate::Guard
+///
+/// See [`RawMutex`] for details.
+///
+/// # Examples
+///
+/// ```
+/// static DATA: interrupts::Mutex<Vec<u32>> = interrupts::Mutex::new(Vec::new());
+///
+/// // interrupts may or may not be enabled
+/// let mut data = DATA.lock();
+/// // interrupts are disabled
+/// data.push(1);
+/// drop(data);
+/// // interrupts are restored to the previous state
+/// ```
+#[cfg(feature = "lock_api")]
+pub type Mutex<T> = lock_api::Mutex<RawMutex, T>;
+
+/// A guard that releases a [`Mutex`] and restores interrupts when dropped.
+#[cfg(feature = "lock_api")]
+pub type MutexGuard<'a, T> = lock_api::MutexGuard<'a, RawMutex, T>;
+
+/// A [`MutexGuard`] for a component of the protected data.
+#[cfg(feature = "lock_api")]
+pub type MappedMutexGuard<'a, T> = lock_api::MappedMutexGuard<'a, RawMutex, T>;
+
+#[cfg(all(
+    test,
+    unix,
+    not(miri),
+    feature = "lock_api",
+    not(feature = "lazy-signals")
+))]
+mod tests {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use nix::libc;
+    use nix::sys::signal::{self, SigHandler, Signal};
+
+    use super::*;
+
+    #[test]
+    fn signals() {
+        static MUTEX: Mutex<usize> = Mutex::new(0);
+        static HANDLER_RAN: AtomicBool = AtomicBool::new(false);
+
+        extern "C" fn handle_sigwinch(_signal: libc::c_int) {
+            *MUTEX.lock() += 1;
+            HANDLER_RAN.store(true, Ordering::Relaxed);
+        }
+
+        let handler = SigHandler::Handler(handle_sigwinch);
+        unsafe { signal::signal(Signal::SIGWINCH, handler) }.unwrap();
+
+        let mut guard = MUTEX.lock();
+        assert!(!crate::are_enabled());
+        assert!(MUTEX.try_lock().is_none());
+        signal::raise(Signal::SIGWINCH).unwrap();
+        assert!(!HANDLER_RAN.load(Ordering::Relaxed));
+        *guard += 1;
+        drop(guard);
+        assert!(HANDLER_RAN.load(Ordering::Relaxed));
+        assert_eq!(*MUTEX.lock(), 2);
+    }
+}
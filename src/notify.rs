@@ -0,0 +1,194 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::mutex::RawMutex;
+
+/// A notification that interrupt handlers or signal handlers can send to a task.
+///
+/// [`notify`] may be called from any context, including interrupt handlers and signal handlers.
+/// With `lazy-signals`, signal handlers have to be installed via `unix::set_handler`, since guards in other handlers would run deferred work and `tracing` subscribers.
+/// [`wait`] returns a future that completes once a notification has been sent.
+///
+/// Notifications are not counted:
+/// notifying several times before the task is polled completes the future only once.
+///
+/// This does not allocate and can be placed in a `static`.
+///
+/// [`notify`]: Self::notify
+/// [`wait`]: Self::wait
+///
+/// # Examples
+///
+/// ```
+/// static NOTIFY: interrupts::Notify = interrupts::Notify::new();
+///
+/// fn interrupt_handler() {
+///     NOTIFY.notify();
+/// }
+///
+/// async fn task() {
+///     NOTIFY.wait().await;
+///     // the interrupt has arrived
+/// }
+/// ```
+pub struct Notify {
+    notified: AtomicBool,
+    lock: RawMutex,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// SAFETY: `waker` is only accessed while holding `lock`.
+unsafe impl Sync for Notify {}
+
+impl Notify {
+    /// Creates a new notification that has not been sent yet.
+    pub const fn new() -> Self {
+        Self {
+            notified: AtomicBool::new(false),
+            lock: RawMutex::new(),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Sends a notification, waking the waiting task if any.
+    ///
+    /// The waker is woken after the internal lock has been released.
+    /// When calling this from an interrupt handler or signal handler, the waker must be safe to wake from that context.
+    pub fn notify(&self) {
+        self.notified.store(true, Ordering::Release);
+
+        let waker = self.with_waker(Option::take);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Returns a future that completes once a notification has been sent.
+    ///
+    /// Completing the future consumes the notification.
+    /// Only the most recently polled future is woken.
+    pub fn wait(&self) -> Wait<'_> {
+        Wait { notify: self }
+    }
+
+    /// Runs `f` on the registered waker while holding the lock with interrupts disabled.
+    fn with_waker<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Option<Waker>) -> R,
+    {
+        /// Releases the lock even if `f` panics.
+        struct Release<'a>(&'a RawMutex);
+
+        impl Drop for Release<'_> {
+            fn drop(&mut self) {
+                // SAFETY: The lock was acquired below.
+                unsafe { self.0.release() }
+            }
+        }
+
+        self.lock.acquire();
+        let _release = Release(&self.lock);
+        // SAFETY: We hold the lock.
+        f(unsafe { &mut *self.waker.get() })
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notify")
+            .field("notified", &self.notified)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A future that completes once a [`Notify`] has been notified.
+///
+/// Created using [`Notify::wait`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Wait<'a> {
+    notify: &'a Notify,
+}
+
+impl Future for Wait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Registering the waker while holding the lock with interrupts disabled avoids losing a notification
+        // between checking the flag and registering the waker.
+        let (ready, old) = self.notify.with_waker(|waker| {
+            if self.notify.notified.swap(false, Ordering::Acquire) {
+                return (true, None);
+            }
+
+            let old = match waker {
+                Some(waker) if waker.will_wake(cx.waker()) => None,
+                waker => waker.replace(cx.waker().clone()),
+            };
+            (false, old)
+        });
+        // The old waker is dropped after the lock has been released.
+        drop(old);
+
+        if ready {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(all(test, unix, not(miri)))]
+mod tests {
+    use core::pin::pin;
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use nix::libc;
+    use nix::sys::signal::{self, SigHandler, Signal};
+
+    use super::*;
+
+    #[test]
+    fn signals() {
+        static NOTIFY: Notify = Notify::new();
+
+        struct Flag(AtomicBool);
+
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+
+        extern "C" fn handle_sigpipe(_signal: libc::c_int) {
+            NOTIFY.notify();
+        }
+
+        let handler = SigHandler::Handler(handle_sigpipe);
+        unsafe { signal::signal(Signal::SIGPIPE, handler) }.unwrap();
+
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut wait = pin!(NOTIFY.wait());
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Pending);
+        signal::raise(Signal::SIGPIPE).unwrap();
+        assert!(flag.0.load(Ordering::Relaxed));
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(()));
+
+        let mut wait = pin!(NOTIFY.wait());
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Pending);
+    }
+}
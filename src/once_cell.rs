@@ -0,0 +1,401 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{fmt, hint, mem};
+
+use crate::local;
+
+/// The cell is uninitialized.
+const UNINIT: usize = 0;
+/// The cell is initialized.
+///
+/// Any other value means that the CPU with that [`local::id`] is initializing the cell.
+const COMPLETE: usize = 1;
+/// A CPU without [`local::id`] is initializing the cell.
+///
+/// This is never the address of a `CpuLocal`, which is aligned.
+const UNKNOWN: usize = usize::MAX;
+
+/// An error indicating that a cell was accessed during its own initialization on the same CPU.
+///
+/// This happens if the initialization function accesses the cell recursively or if an interrupt handler that cannot be masked (such as an NMI or a synchronous signal) accesses the cell.
+/// Waiting for the initialization to complete would deadlock in that case.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReentrantInitError;
+
+impl fmt::Display for ReentrantInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cell was accessed during its own initialization")
+    }
+}
+
+impl core::error::Error for ReentrantInitError {}
+
+/// A thread-safe cell which can be written to only once, initialized with interrupts disabled.
+///
+/// This is like [`std::sync::OnceLock`], but the initialization function runs with interrupts disabled.
+/// Thus, an interrupt handler or signal handler cannot interrupt the initialization on the same CPU and deadlock while waiting for it.
+/// Other CPUs spin until the initialization is complete.
+///
+/// If the cell is accessed during its own initialization on the same CPU, [`try_get_or_init`] returns an error and [`get_or_init`] panics instead of deadlocking.
+///
+/// On bare-metal targets, this requires `set_cpu_local` to tell CPUs apart, unless the `unsafe-assume-single-core` feature is enabled.
+/// Otherwise, reentrant access cannot be detected and deadlocks instead.
+///
+/// [`std::sync::OnceLock`]: https://doc.rust-lang.org/std/sync/struct.OnceLock.html
+/// [`try_get_or_init`]: Self::try_get_or_init
+/// [`get_or_init`]: Self::get_or_init
+///
+/// # Examples
+///
+/// ```
+/// static CELL: interrupts::OnceCell<u32> = interrupts::OnceCell::new();
+///
+/// assert_eq!(CELL.get(), None);
+/// // interrupts are disabled during initialization
+/// assert_eq!(*CELL.get_or_init(|| 42), 42);
+/// assert_eq!(CELL.get(), Some(&42));
+/// ```
+pub struct OnceCell<T> {
+    state: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+unsafe impl<T: Send> Send for OnceCell<T> {}
+
+impl<T> OnceCell<T> {
+    /// Creates a new empty cell.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(UNINIT),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    #[inline]
+    fn is_initialized(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    /// Gets the reference to the underlying value.
+    ///
+    /// Returns `None` if the cell is uninitialized or being initialized.
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        if self.is_initialized() {
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Gets the mutable reference to the underlying value.
+    ///
+    /// Returns `None` if the cell is uninitialized.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if *self.state.get_mut() == COMPLETE {
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Initializes the contents of the cell to `value`.
+    ///
+    /// Returns `Err(value)` if the cell was already initialized or if it is being initialized on the same CPU.
+    /// If the cell is being initialized on another CPU, this waits for the initialization to complete.
+    #[inline]
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        // `value` is only taken if this call initializes the cell.
+        let _ = self.initialize(|| value.take().unwrap());
+        match value {
+            Some(value) => Err(value),
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the contents of the cell, initializing it with `f` if the cell was uninitialized.
+    ///
+    /// `f` runs with interrupts disabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell is accessed during its own initialization on the same CPU.
+    /// See [`try_get_or_init`](Self::try_get_or_init).
+    ///
+    /// If `f` panics, the panic is propagated to the caller and the cell remains uninitialized.
+    #[inline]
+    #[track_caller]
+    pub fn get_or_init<F>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        match self.try_get_or_init(f) {
+            Ok(value) => value,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Gets the contents of the cell, initializing it with `f` if the cell was uninitialized.
+    ///
+    /// `f` runs with interrupts disabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cell is accessed during its own initialization on the same CPU.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interrupts::{OnceCell, ReentrantInitError};
+    ///
+    /// static CELL: OnceCell<u32> = OnceCell::new();
+    ///
+    /// let value = CELL.try_get_or_init(|| {
+    ///     assert_eq!(CELL.try_get_or_init(|| 1), Err(ReentrantInitError));
+    ///     2
+    /// });
+    /// assert_eq!(value, Ok(&2));
+    /// ```
+    #[inline]
+    pub fn try_get_or_init<F>(&self, f: F) -> Result<&T, ReentrantInitError>
+    where
+        F: FnOnce() -> T,
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+
+        self.initialize(f)?;
+
+        Ok(unsafe { (*self.value.get()).assume_init_ref() })
+    }
+
+    #[cold]
+    fn initialize<F>(&self, f: F) -> Result<(), ReentrantInitError>
+    where
+        F: FnOnce() -> T,
+    {
+        /// Resets the state if `f` panics.
+        struct Reset<'a>(&'a AtomicUsize);
+
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.store(UNINIT, Ordering::Release);
+            }
+        }
+
+        crate::without(|| {
+            let id = local::id();
+            let owner_id = id.unwrap_or(UNKNOWN);
+            loop {
+                match self.state.compare_exchange_weak(
+                    UNINIT,
+                    owner_id,
+                    Ordering::Acquire,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => {
+                        let reset = Reset(&self.state);
+                        let value = f();
+                        unsafe {
+                            (*self.value.get()).write(value);
+                        }
+                        mem::forget(reset);
+                        self.state.store(COMPLETE, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(COMPLETE) => return Ok(()),
+                    Err(owner) if Some(owner) == id => return Err(ReentrantInitError),
+                    Err(_) => hint::spin_loop(),
+                }
+            }
+        })
+    }
+
+    /// Consumes the cell, returning the wrapped value.
+    ///
+    /// Returns `None` if the cell was uninitialized.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    /// Takes the value out of this cell, moving it back to an uninitialized state.
+    ///
+    /// Returns `None` if the cell was uninitialized.
+    #[inline]
+    pub fn take(&mut self) -> Option<T> {
+        if *self.state.get_mut() == COMPLETE {
+            *self.state.get_mut() = UNINIT;
+            Some(unsafe { self.value.get_mut().assume_init_read() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_tuple("OnceCell");
+        match self.get() {
+            Some(v) => d.field(v),
+            None => d.field(&format_args!("<uninit>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T> From<T> for OnceCell<T> {
+    fn from(value: T) -> Self {
+        Self {
+            state: AtomicUsize::new(COMPLETE),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.value.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+/// A value which is initialized on the first access, with interrupts disabled.
+///
+/// This is like [`std::sync::LazyLock`], but built on [`OnceCell`].
+/// See [`OnceCell`] for details on initialization.
+///
+/// [`std::sync::LazyLock`]: https://doc.rust-lang.org/std/sync/struct.LazyLock.html
+///
+/// # Examples
+///
+/// ```
+/// static VALUE: interrupts::Lazy<u32> = interrupts::Lazy::new(|| 42);
+///
+/// // interrupts are disabled during initialization
+/// assert_eq!(*VALUE, 42);
+/// ```
+pub struct Lazy<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: UnsafeCell<Option<F>>,
+}
+
+// SAFETY: `init` is only accessed by the CPU initializing `cell`.
+unsafe impl<T, F: Send> Sync for Lazy<T, F> where OnceCell<T>: Sync {}
+
+impl<T, F> Lazy<T, F> {
+    /// Creates a new lazy value with the given initializing function.
+    #[inline]
+    pub const fn new(f: F) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init: UnsafeCell::new(Some(f)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Forces the evaluation of this lazy value and returns a reference to the result.
+    ///
+    /// This is equivalent to the `Deref` implementation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is accessed during its own initialization on the same CPU or if a previous initialization panicked.
+    #[inline]
+    #[track_caller]
+    pub fn force(this: &Self) -> &T {
+        match Self::try_force(this) {
+            Ok(value) => value,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Forces the evaluation of this lazy value and returns a reference to the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is accessed during its own initialization on the same CPU.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a previous initialization panicked.
+    #[inline]
+    pub fn try_force(this: &Self) -> Result<&T, ReentrantInitError> {
+        this.cell.try_get_or_init(|| {
+            // SAFETY: Only the CPU initializing `cell` accesses `init`.
+            match unsafe { (*this.init.get()).take() } {
+                Some(f) => f(),
+                None => panic!("Lazy instance has previously been poisoned"),
+            }
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_tuple("Lazy");
+        match self.cell.get() {
+            Some(v) => d.field(v),
+            None => d.field(&format_args!("<uninit>")),
+        };
+        d.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn reentrant() {
+        let cell = OnceCell::new();
+        let value = cell.try_get_or_init(|| {
+            assert!(!crate::are_enabled());
+            assert_eq!(cell.try_get_or_init(|| 1), Err(ReentrantInitError));
+            assert_eq!(cell.set(1), Err(1));
+            2
+        });
+        assert_eq!(value, Ok(&2));
+        assert_eq!(cell.set(3), Err(3));
+    }
+
+    #[test]
+    fn threads() {
+        static CELL: OnceCell<usize> = OnceCell::new();
+
+        let threads = (0..4)
+            .map(|i| thread::spawn(move || *CELL.get_or_init(|| i)))
+            .collect::<Vec<_>>();
+        let values = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+        assert!(values.iter().all(|value| *value == values[0]));
+    }
+}
@@ -0,0 +1,261 @@
+//! Preemption control.
+//!
+//! Disabling preemption prevents the scheduler from switching tasks on the current CPU, while leaving interrupts enabled.
+//! This crate only tracks the per-CPU (per-thread on Unix) preemption count.
+//! The scheduler itself has to query [`is_preemptible`] before switching tasks and registers a hook via [`set_reschedule_hook`] to catch up on deferred rescheduling.
+//!
+//! # Interaction with interrupts
+//!
+//! Disabling interrupts implies disabling preemption:
+//! [`is_preemptible`] returns `false` while interrupts are disabled, for example while a [`Guard`] is held.
+//! If a reschedule was requested in the meantime, it is carried out once the last [`PreemptGuard`] or the outermost [`Guard`] is dropped, whichever comes last.
+//!
+//! [`Guard`]: crate::Guard
+//!
+//! # Examples
+//!
+//! ```
+//! // preemption may or may not be enabled
+//! let guard = interrupts::preempt::disable();
+//! // preemption is disabled, interrupts are unaffected
+//! assert!(!interrupts::preempt::is_preemptible());
+//! drop(guard);
+//! // preemption is restored to the previous state
+//! ```
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+use crate::hook::Hook;
+use crate::{imp, local};
+
+static RESCHEDULE_HOOK: Hook<fn()> = Hook::new();
+
+/// Per-CPU preemption state.
+pub(crate) struct State {
+    count: Cell<usize>,
+    need_resched: Cell<bool>,
+}
+
+impl State {
+    pub const fn new() -> Self {
+        Self {
+            count: Cell::new(0),
+            need_resched: Cell::new(false),
+        }
+    }
+}
+
+/// Temporarily disable preemption.
+///
+/// Preemption is enabled again once the returned [`PreemptGuard`] and all other preemption guards on this CPU are dropped.
+///
+/// # Panics
+///
+/// On bare-metal targets, panics if `set_cpu_local` has not been called and the `unsafe-assume-single-core` feature is disabled.
+/// A preemption count shared by all CPUs would race.
+///
+/// # Examples
+///
+/// ```
+/// // preemption may or may not be enabled
+/// let guard = interrupts::preempt::disable();
+/// // preemption is disabled
+/// drop(guard);
+/// // preemption is restored to the previous state
+/// ```
+#[inline]
+pub fn disable() -> PreemptGuard {
+    with_state(|local| {
+        let count = &local.preempt.count;
+        count.set(count.get() + 1);
+    });
+
+    PreemptGuard {
+        _not_send: PhantomData,
+    }
+}
+
+/// A preemption guard.
+///
+/// Created using [`disable`].
+///
+/// While an instance of this guard is held, preemption is disabled on the current CPU.
+/// Unlike [`Guard`], preemption guards are counted, so they may be dropped in any order.
+///
+/// When the last preemption guard is dropped and a reschedule was requested in the meantime, the [reschedule hook] is called.
+///
+/// [`Guard`]: crate::Guard
+/// [reschedule hook]: set_reschedule_hook
+pub struct PreemptGuard {
+    /// The preemption count is per CPU.
+    ///
+    /// Making PreemptGuard `!Send` avoids disabling preemption on one CPU and enabling it on another.
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl PreemptGuard {
+    /// ```compile_fail
+    /// fn send<T: Send>(_: T) {}
+    ///
+    /// send(interrupts::preempt::disable());
+    /// ```
+    fn _dummy() {}
+}
+
+impl Drop for PreemptGuard {
+    #[inline]
+    fn drop(&mut self) {
+        let count = with_state(|local| {
+            let count = &local.preempt.count;
+            let new = count.get() - 1;
+            count.set(new);
+            new
+        });
+
+        if count == 0 {
+            reschedule_if_needed();
+        }
+    }
+}
+
+/// Run a closure with preemption disabled.
+///
+/// # Examples
+///
+/// ```
+/// // preemption may or may not be enabled
+/// interrupts::preempt::without(|| {
+///     // preemption is disabled
+/// });
+/// // preemption is restored to the previous state
+/// ```
+#[inline]
+pub fn without<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let guard = disable();
+
+    let ret = f();
+
+    drop(guard);
+
+    ret
+}
+
+/// Returns the number of preemption guards held on the current CPU.
+#[inline]
+pub fn count() -> usize {
+    // Without CPU-local state, no guard can have been created.
+    local::try_with(|local| local.preempt.count.get()).unwrap_or(0)
+}
+
+/// Returns whether the current CPU may be preempted.
+///
+/// This is the case if no [`PreemptGuard`] is held and interrupts are enabled.
+#[inline]
+pub fn is_preemptible() -> bool {
+    count() == 0 && imp::are_enabled()
+}
+
+/// Request a reschedule of the current CPU.
+///
+/// This is usually called by the scheduler's timer interrupt handler if it would like to preempt the current task but [`is_preemptible`] returns `false`.
+/// The [reschedule hook] is called once preemption becomes possible again.
+///
+/// # Panics
+///
+/// On bare-metal targets, panics if `set_cpu_local` has not been called and the `unsafe-assume-single-core` feature is disabled.
+///
+/// [reschedule hook]: set_reschedule_hook
+#[inline]
+pub fn request_reschedule() {
+    with_state(|local| local.preempt.need_resched.set(true));
+}
+
+/// Set the hook that is called when a requested reschedule becomes possible.
+///
+/// The hook is called when the last [`PreemptGuard`] or the outermost [`Guard`] is dropped, if [`request_reschedule`] has been called in the meantime.
+/// It runs with preemption and interrupts enabled.
+/// Pass `None` to remove the hook.
+///
+/// [`Guard`]: crate::Guard
+///
+/// # Examples
+///
+/// ```
+/// fn schedule() {
+///     // switch to another task
+/// }
+///
+/// interrupts::preempt::set_reschedule_hook(Some(schedule));
+/// ```
+pub fn set_reschedule_hook(hook: Option<fn()>) {
+    RESCHEDULE_HOOK.set(hook);
+}
+
+/// Call the reschedule hook if a reschedule was requested and the current CPU is preemptible.
+#[inline]
+pub(crate) fn reschedule_if_needed() {
+    // This runs when dropping guards and thus must not panic.
+    let need_resched = local::try_with(|local| local.preempt.need_resched.get()).unwrap_or(false);
+    if !need_resched || !is_preemptible() {
+        return;
+    }
+
+    with_state(|local| local.preempt.need_resched.set(false));
+    if let Some(hook) = RESCHEDULE_HOOK.get() {
+        hook();
+    }
+}
+
+/// Runs `f` with the current CPU's state, which must not be shared between CPUs.
+#[inline]
+#[track_caller]
+fn with_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&local::CpuLocal) -> R,
+{
+    match local::try_with(f) {
+        Some(ret) => ret,
+        None => panic!("preemption control requires `set_cpu_local`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    std::thread_local! {
+        static RESCHEDULED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[test]
+    fn reschedule() {
+        fn schedule() {
+            assert!(is_preemptible());
+            RESCHEDULED.set(RESCHEDULED.get() + 1);
+        }
+
+        set_reschedule_hook(Some(schedule));
+
+        assert!(is_preemptible());
+        let outer = disable();
+        let inner = disable();
+        assert_eq!(count(), 2);
+        request_reschedule();
+        drop(outer);
+        assert_eq!(RESCHEDULED.get(), 0);
+        drop(inner);
+        assert_eq!(RESCHEDULED.get(), 1);
+
+        crate::without(|| {
+            assert!(!is_preemptible());
+            request_reschedule();
+            without(|| {});
+            assert_eq!(RESCHEDULED.get(), 1);
+        });
+        assert_eq!(RESCHEDULED.get(), 2);
+    }
+}
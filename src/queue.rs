@@ -0,0 +1,326 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fixed-capacity, lock-free single-producer single-consumer queue.
+///
+/// This queue is meant for passing data between interrupt handlers or signal handlers and thread code in either direction.
+/// Neither side ever blocks the other, so the producer and the consumer may each run with interrupts disabled.
+///
+/// The queue holds up to `N` elements.
+/// Use [`split`] to obtain the [`Producer`] and the [`Consumer`].
+/// For queues in a `static`, [`producer_unchecked`] and [`consumer_unchecked`] can be used instead.
+///
+/// [`split`]: Self::split
+/// [`producer_unchecked`]: Self::producer_unchecked
+/// [`consumer_unchecked`]: Self::consumer_unchecked
+///
+/// # Examples
+///
+/// ```
+/// let mut queue = interrupts::Queue::<u32, 4>::new();
+/// let (mut producer, mut consumer) = queue.split();
+///
+/// producer.enqueue(1).unwrap();
+/// producer.enqueue(2).unwrap();
+/// assert_eq!(consumer.dequeue(), Some(1));
+/// assert_eq!(consumer.dequeue(), Some(2));
+/// assert_eq!(consumer.dequeue(), None);
+/// ```
+///
+/// Passing data from an interrupt handler to thread code:
+///
+/// ```
+/// static QUEUE: interrupts::Queue<u32, 16> = interrupts::Queue::new();
+///
+/// fn interrupt_handler() {
+///     // SAFETY: Only this handler produces.
+///     let mut producer = unsafe { QUEUE.producer_unchecked() };
+///     let _ = producer.enqueue(42);
+/// }
+///
+/// fn thread() -> u32 {
+///     // SAFETY: Only this thread consumes.
+///     let mut consumer = unsafe { QUEUE.consumer_unchecked() };
+///     // waits for interrupts until an element is available
+///     consumer.dequeue_blocking()
+/// }
+/// ```
+pub struct Queue<T, const N: usize> {
+    /// The index of the next element to dequeue, modulo `2 * N`.
+    head: AtomicUsize,
+    /// The index of the next element to enqueue, modulo `2 * N`.
+    ///
+    /// Counting modulo `2 * N` distinguishes a full queue from an empty one.
+    tail: AtomicUsize,
+    buf: [UnsafeCell<MaybeUninit<T>>; N],
+}
+
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+unsafe impl<T: Send, const N: usize> Send for Queue<T, N> {}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// Creates a new, empty queue.
+    ///
+    /// Queues must have a capacity of at least 1:
+    ///
+    /// ```compile_fail
+    /// let queue = interrupts::Queue::<u32, 0>::new();
+    /// ```
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "queue capacity must not be zero") };
+
+        Self {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            buf: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+        }
+    }
+
+    /// Returns the maximum number of elements this queue can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in this queue.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        (tail + 2 * N - head) % (2 * N)
+    }
+
+    /// Returns `true` if this queue contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits this queue into its producer and consumer ends.
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+
+    /// Returns the producer end of this queue.
+    ///
+    /// # Safety
+    ///
+    /// At most one [`Producer`] of this queue may exist at any time.
+    pub unsafe fn producer_unchecked(&self) -> Producer<'_, T, N> {
+        Producer { queue: self }
+    }
+
+    /// Returns the consumer end of this queue.
+    ///
+    /// # Safety
+    ///
+    /// At most one [`Consumer`] of this queue may exist at any time.
+    pub unsafe fn consumer_unchecked(&self) -> Consumer<'_, T, N> {
+        Consumer { queue: self }
+    }
+
+    fn increment(index: usize) -> usize {
+        (index + 1) % (2 * N)
+    }
+
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.buf[index % N].get()
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        let mut consumer = Consumer { queue: self };
+        while consumer.dequeue().is_some() {}
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Queue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The producer end of a [`Queue`].
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Appends `value` to the queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns `value` back if the queue is full.
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        let queue = self.queue;
+        let tail = queue.tail.load(Ordering::Relaxed);
+        let head = queue.head.load(Ordering::Acquire);
+        if (tail + 2 * N - head) % (2 * N) == N {
+            return Err(value);
+        }
+
+        // SAFETY: The slot is not part of the queue, and we are the only producer.
+        unsafe {
+            (*queue.slot(tail)).write(value);
+        }
+        queue
+            .tail
+            .store(Queue::<T, N>::increment(tail), Ordering::Release);
+        Ok(())
+    }
+
+    /// Appends `value` to the queue, waiting for interrupts while it is full.
+    ///
+    /// This is meant for thread code that passes data to interrupt handlers.
+    /// If the queue is full, interrupts are enabled and this waits for the next interrupt using [`Guard::enable_and_wait`] before retrying.
+    /// If interrupts are already disabled, this spins instead.
+    ///
+    /// [`Guard::enable_and_wait`]: crate::Guard::enable_and_wait
+    pub fn enqueue_blocking(&mut self, mut value: T) {
+        loop {
+            let guard = crate::disable();
+            match self.enqueue(value) {
+                Ok(()) => return,
+                Err(v) => value = v,
+            }
+            guard.enable_and_wait();
+        }
+    }
+
+    /// Returns `true` if the queue is full.
+    pub fn is_full(&self) -> bool {
+        self.queue.len() == N
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Producer<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer").finish_non_exhaustive()
+    }
+}
+
+/// The consumer end of a [`Queue`].
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+impl<T, const N: usize> Consumer<'_, T, N> {
+    /// Removes the first element from the queue and returns it.
+    ///
+    /// Returns `None` if the queue is empty.
+    pub fn dequeue(&mut self) -> Option<T> {
+        let queue = self.queue;
+        let head = queue.head.load(Ordering::Relaxed);
+        let tail = queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        // SAFETY: The slot is part of the queue and was initialized by the producer.
+        // We are the only consumer.
+        let value = unsafe { (*queue.slot(head)).assume_init_read() };
+        queue
+            .head
+            .store(Queue::<T, N>::increment(head), Ordering::Release);
+        Some(value)
+    }
+
+    /// Removes the first element from the queue, waiting for interrupts while it is empty.
+    ///
+    /// This is meant for thread code that receives data from interrupt handlers.
+    /// If the queue is empty, interrupts are enabled and this waits for the next interrupt using [`Guard::enable_and_wait`] before retrying.
+    /// If interrupts are already disabled, this spins instead.
+    ///
+    /// [`Guard::enable_and_wait`]: crate::Guard::enable_and_wait
+    pub fn dequeue_blocking(&mut self) -> T {
+        loop {
+            let guard = crate::disable();
+            if let Some(value) = self.dequeue() {
+                return value;
+            }
+            guard.enable_and_wait();
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Consumer<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo() {
+        let mut queue = Queue::<usize, 3>::new();
+        let (mut producer, mut consumer) = queue.split();
+
+        for i in 0..10 {
+            producer.enqueue(2 * i).unwrap();
+            producer.enqueue(2 * i + 1).unwrap();
+            assert_eq!(consumer.dequeue(), Some(2 * i));
+            assert_eq!(consumer.dequeue(), Some(2 * i + 1));
+        }
+
+        producer.enqueue(0).unwrap();
+        producer.enqueue(1).unwrap();
+        producer.enqueue(2).unwrap();
+        assert!(producer.is_full());
+        assert_eq!(producer.enqueue(3), Err(3));
+        assert_eq!(consumer.dequeue(), Some(0));
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[cfg(all(unix, not(miri), not(feature = "lazy-signals")))]
+    #[test]
+    fn signals() {
+        use std::thread;
+        use std::time::Duration;
+
+        use nix::libc;
+        use nix::sys::signal::{self, SigHandler, Signal};
+
+        static QUEUE: Queue<u32, 4> = Queue::new();
+
+        extern "C" fn handle_sigvtalrm(_signal: libc::c_int) {
+            let mut producer = unsafe { QUEUE.producer_unchecked() };
+            producer.enqueue(42).unwrap();
+        }
+
+        let handler = SigHandler::Handler(handle_sigvtalrm);
+        unsafe { signal::signal(Signal::SIGVTALRM, handler) }.unwrap();
+
+        let mut consumer = unsafe { QUEUE.consumer_unchecked() };
+        let guard = crate::disable();
+        signal::raise(Signal::SIGVTALRM).unwrap();
+        assert_eq!(consumer.dequeue(), None);
+        drop(guard);
+        assert_eq!(consumer.dequeue(), Some(42));
+
+        let main = unsafe { libc::pthread_self() };
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            let res = unsafe { libc::pthread_kill(main, libc::SIGVTALRM) };
+            assert_eq!(res, 0);
+        });
+        assert_eq!(consumer.dequeue_blocking(), 42);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,350 @@
+use core::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut};
+use core::ops::{Deref, DerefMut};
+use core::{fmt, mem};
+
+use crate::Guard;
+
+/// A mutable memory location with dynamically checked borrow rules that disables interrupts while borrowed.
+///
+/// This is like [`RefCell`], but interrupts are disabled for as long as a borrow is held.
+/// This allows sharing data with interrupt handlers or signal handlers on the same CPU or thread.
+/// A handler can never observe the cell being borrowed, since it cannot run while a borrow exists.
+///
+/// In contrast to `Mutex`, this type is not [`Sync`] and thus only suited for CPU-local or thread-local data.
+///
+/// # Examples
+///
+/// ```
+/// use interrupts::InterruptRefCell;
+///
+/// let cell = InterruptRefCell::new(0);
+///
+/// // interrupts may or may not be enabled
+/// let mut value = cell.borrow_mut();
+/// // interrupts are disabled
+/// *value += 1;
+/// drop(value);
+/// // interrupts are restored to the previous state
+/// ```
+pub struct InterruptRefCell<T: ?Sized> {
+    inner: RefCell<T>,
+}
+
+impl<T> InterruptRefCell<T> {
+    /// Creates a new `InterruptRefCell` containing `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: RefCell::new(value),
+        }
+    }
+
+    /// Consumes the `InterruptRefCell`, returning the wrapped value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    /// Replaces the wrapped value with a new one, returning the old value.
+    ///
+    /// See [`RefCell::replace`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    #[inline]
+    #[track_caller]
+    pub fn replace(&self, t: T) -> T {
+        mem::replace(&mut *self.borrow_mut(), t)
+    }
+
+    /// Replaces the wrapped value with a new one computed from `f`, returning the old value.
+    ///
+    /// See [`RefCell::replace_with`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    #[inline]
+    #[track_caller]
+    pub fn replace_with<F: FnOnce(&mut T) -> T>(&self, f: F) -> T {
+        let mut_borrow = &mut *self.borrow_mut();
+        let replacement = f(mut_borrow);
+        mem::replace(mut_borrow, replacement)
+    }
+
+    /// Swaps the wrapped value of `self` with the wrapped value of `other`.
+    ///
+    /// See [`RefCell::swap`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value in either `InterruptRefCell` is currently borrowed.
+    #[inline]
+    pub fn swap(&self, other: &Self) {
+        mem::swap(&mut *self.borrow_mut(), &mut *other.borrow_mut())
+    }
+}
+
+impl<T: ?Sized> InterruptRefCell<T> {
+    /// Immutably borrows the wrapped value, disabling interrupts until the returned borrow is dropped.
+    ///
+    /// See [`RefCell::borrow`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently mutably borrowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interrupts::InterruptRefCell;
+    ///
+    /// let cell = InterruptRefCell::new(5);
+    ///
+    /// let borrowed = cell.borrow();
+    /// // interrupts are disabled
+    /// assert_eq!(*borrowed, 5);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn borrow(&self) -> InterruptRef<'_, T> {
+        self.try_borrow().expect("already mutably borrowed")
+    }
+
+    /// Immutably borrows the wrapped value, returning an error if the value is currently mutably borrowed.
+    ///
+    /// See [`RefCell::try_borrow`].
+    #[inline]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    pub fn try_borrow(&self) -> Result<InterruptRef<'_, T>, BorrowError> {
+        let guard = crate::disable();
+        let inner = self.inner.try_borrow()?;
+        Ok(InterruptRef { inner, guard })
+    }
+
+    /// Mutably borrows the wrapped value, disabling interrupts until the returned borrow is dropped.
+    ///
+    /// See [`RefCell::borrow_mut`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interrupts::InterruptRefCell;
+    ///
+    /// let cell = InterruptRefCell::new(5);
+    ///
+    /// let mut borrowed = cell.borrow_mut();
+    /// // interrupts are disabled
+    /// *borrowed = 6;
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn borrow_mut(&self) -> InterruptRefMut<'_, T> {
+        self.try_borrow_mut().expect("already borrowed")
+    }
+
+    /// Mutably borrows the wrapped value, returning an error if the value is currently borrowed.
+    ///
+    /// See [`RefCell::try_borrow_mut`].
+    #[inline]
+    #[cfg_attr(feature = "track-caller", track_caller)]
+    pub fn try_borrow_mut(&self) -> Result<InterruptRefMut<'_, T>, BorrowMutError> {
+        let guard = crate::disable();
+        let inner = self.inner.try_borrow_mut()?;
+        Ok(InterruptRefMut { inner, guard })
+    }
+
+    /// Returns a raw pointer to the underlying data in this cell.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T {
+        self.inner.as_ptr()
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// This does not disable interrupts, since the mutable borrow statically guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: Default> InterruptRefCell<T> {
+    /// Takes the wrapped value, leaving `Default::default()` in its place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    #[inline]
+    #[track_caller]
+    pub fn take(&self) -> T {
+        self.replace(Default::default())
+    }
+}
+
+impl<T: Clone> Clone for InterruptRefCell<T> {
+    #[inline]
+    #[track_caller]
+    fn clone(&self) -> Self {
+        Self::new(self.borrow().clone())
+    }
+}
+
+impl<T: Default> Default for InterruptRefCell<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T> From<T> for InterruptRefCell<T> {
+    fn from(t: T) -> Self {
+        Self::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for InterruptRefCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::without(|| self.inner.fmt(f))
+    }
+}
+
+/// Wraps a borrowed reference to a value in an [`InterruptRefCell`].
+///
+/// Interrupts are disabled while this borrow is held.
+/// When dropped, the borrow is released before interrupts are restored.
+pub struct InterruptRef<'b, T: ?Sized + 'b> {
+    // Fields are dropped in declaration order.
+    inner: Ref<'b, T>,
+    guard: Guard,
+}
+
+impl<'b, T: ?Sized> InterruptRef<'b, T> {
+    /// Makes a new `InterruptRef` for a component of the borrowed data.
+    ///
+    /// See [`Ref::map`].
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> InterruptRef<'b, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let Self { inner, guard } = orig;
+        InterruptRef {
+            inner: Ref::map(inner, f),
+            guard,
+        }
+    }
+
+    /// Makes a new `InterruptRef` for an optional component of the borrowed data.
+    ///
+    /// See [`Ref::filter_map`].
+    #[inline]
+    #[allow(clippy::result_large_err)]
+    pub fn filter_map<U: ?Sized, F>(orig: Self, f: F) -> Result<InterruptRef<'b, U>, Self>
+    where
+        F: FnOnce(&T) -> Option<&U>,
+    {
+        let Self { inner, guard } = orig;
+        match Ref::filter_map(inner, f) {
+            Ok(inner) => Ok(InterruptRef { inner, guard }),
+            Err(inner) => Err(Self { inner, guard }),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for InterruptRef<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for InterruptRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for InterruptRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A wrapper type for a mutably borrowed value from an [`InterruptRefCell`].
+///
+/// Interrupts are disabled while this borrow is held.
+/// When dropped, the borrow is released before interrupts are restored.
+pub struct InterruptRefMut<'b, T: ?Sized + 'b> {
+    // Fields are dropped in declaration order.
+    inner: RefMut<'b, T>,
+    guard: Guard,
+}
+
+impl<'b, T: ?Sized> InterruptRefMut<'b, T> {
+    /// Makes a new `InterruptRefMut` for a component of the borrowed data.
+    ///
+    /// See [`RefMut::map`].
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> InterruptRefMut<'b, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let Self { inner, guard } = orig;
+        InterruptRefMut {
+            inner: RefMut::map(inner, f),
+            guard,
+        }
+    }
+
+    /// Makes a new `InterruptRefMut` for an optional component of the borrowed data.
+    ///
+    /// See [`RefMut::filter_map`].
+    #[inline]
+    #[allow(clippy::result_large_err)]
+    pub fn filter_map<U: ?Sized, F>(orig: Self, f: F) -> Result<InterruptRefMut<'b, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        let Self { inner, guard } = orig;
+        match RefMut::filter_map(inner, f) {
+            Ok(inner) => Ok(InterruptRefMut { inner, guard }),
+            Err(inner) => Err(Self { inner, guard }),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for InterruptRefMut<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized> DerefMut for InterruptRefMut<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for InterruptRefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for InterruptRefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
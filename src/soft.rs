@@ -0,0 +1,67 @@
+//! Soft interrupt disabling for bare-metal kernels.
+//!
+//! With the `soft-disable` feature, [`disable`] does not mask interrupts in hardware.
+//! Instead, it only sets a CPU-local flag, which avoids the cost of `cli`/`sti` (x86-64), `msr DAIFSet`/`msr DAIF` (AArch64), or `csrrci`/`csrs` (64-bit RISC-V) on hot paths.
+//! 32-bit RISC-V is not supported.
+//!
+//! Interrupts still arrive while soft-disabled.
+//! Thus, the kernel's interrupt entry has to call [`handler_entry`] before running the actual handler:
+//!
+//! - If it returns `true`, interrupts were not disabled and the handler runs as usual.
+//! - If it returns `false`, the interrupt was recorded as pending.
+//!   The kernel must not run the handler and must return from the interrupt with interrupts masked in hardware.
+//!   To do so, it clears the interrupt enable bit in the saved trap frame (`RFLAGS.IF` on x86-64, `SPSR_EL1.I` on AArch64, `sstatus.SPIE` on 64-bit RISC-V).
+//!   For level-triggered interrupts, the kernel should also acknowledge or mask the interrupt at the interrupt controller.
+//!
+//! Once the outermost [`Guard`] is dropped, each pending interrupt is passed to the [replay hook] with interrupts still disabled.
+//! Afterward, interrupts are unmasked in hardware again.
+//!
+//! [`disable`]: crate::disable
+//! [`Guard`]: crate::Guard
+//! [replay hook]: set_replay_hook
+//!
+//! # Examples
+//!
+//! ```ignore
+//! fn replay(irq: u8) {
+//!     dispatch_irq(irq);
+//! }
+//!
+//! interrupts::soft::set_replay_hook(Some(replay));
+//!
+//! extern "C" fn irq_entry(frame: &mut TrapFrame, irq: u8) {
+//!     if interrupts::soft::handler_entry(irq) {
+//!         dispatch_irq(irq);
+//!     } else {
+//!         frame.mask_interrupts();
+//!     }
+//! }
+//! ```
+
+use crate::imp;
+
+/// Record the interrupt `irq` as pending if interrupts are soft-disabled.
+///
+/// Returns `true` if the interrupt should be handled right away.
+/// Returns `false` if the interrupt was deferred.
+/// In that case, the kernel must return from the interrupt with interrupts masked in hardware.
+/// See the [module documentation] for details.
+///
+/// This must be called with interrupts masked in hardware, which is the case on interrupt entry.
+///
+/// [module documentation]: self
+#[inline]
+pub fn handler_entry(irq: u8) -> bool {
+    imp::handler_entry(irq)
+}
+
+/// Set the hook that replays deferred interrupts.
+///
+/// The hook is called for each interrupt that was deferred by [`handler_entry`], once the outermost [`Guard`] is dropped.
+/// It runs with interrupts disabled, like regular interrupt handlers.
+/// Pass `None` to remove the hook.
+///
+/// [`Guard`]: crate::Guard
+pub fn set_replay_hook(hook: Option<fn(u8)>) {
+    imp::set_replay_hook(hook);
+}
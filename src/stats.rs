@@ -0,0 +1,311 @@
+//! Statistics on how long interrupts are disabled.
+//!
+//! With the `latency-stats` feature, each CPU records how long interrupts were disabled by [`Guard`]s.
+//! Only the outermost guard of a nested sequence is recorded, that is, the guard that actually disabled interrupts.
+//!
+//! Durations are measured in ticks of the platform's timestamp counter:
+//!
+//! | Platform    | Counter                          |
+//! | ----------- | -------------------------------- |
+//! | AArch64     | `CNTVCT_EL0`                     |
+//! | RISC-V 32   | `mcycle` CSR                     |
+//! | RISC-V 64   | `time` CSR (`rdtime`)            |
+//! | x86-64      | TSC (`rdtsc`)                    |
+//! | Unix        | `CLOCK_MONOTONIC` in nanoseconds |
+//! | unsupported | always 0                         |
+//!
+//! With the `track-caller` feature, the call sites that disabled interrupts for the longest time are recorded as well.
+//! See `Snapshot::offenders`.
+//!
+//! [`Guard`]: crate::Guard
+//!
+//! # Examples
+//!
+//! ```
+//! interrupts::stats::reset();
+//!
+//! interrupts::without(|| {
+//!     // interrupts are disabled
+//! });
+//!
+//! let snapshot = interrupts::stats::snapshot();
+//! assert_eq!(snapshot.count(), 1);
+//! println!("worst-case latency: {} ticks", snapshot.max());
+//! ```
+
+use core::cell::Cell;
+#[cfg(feature = "track-caller")]
+use core::fmt;
+#[cfg(feature = "track-caller")]
+use core::panic::Location;
+
+use crate::timing::Section;
+use crate::{imp, local};
+
+/// The number of histogram buckets.
+///
+/// Bucket 0 counts durations of 0 ticks.
+/// Bucket `i > 0` counts durations in `2^(i - 1)..2^i` ticks.
+pub const BUCKETS: usize = u64::BITS as usize + 1;
+
+/// The number of call sites recorded per CPU.
+#[cfg(feature = "track-caller")]
+pub const OFFENDERS: usize = 16;
+
+/// A call site that disabled interrupts.
+#[cfg(feature = "track-caller")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Offender {
+    location: &'static Location<'static>,
+    count: u64,
+    max: u64,
+}
+
+#[cfg(feature = "track-caller")]
+impl Offender {
+    /// Returns the call site that disabled interrupts.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns how often interrupts were disabled from this call site.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the maximum duration that interrupts were disabled from this call site.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+}
+
+#[cfg(feature = "track-caller")]
+impl fmt::Display for Offender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} held interrupts off for {} ticks",
+            self.location.file(),
+            self.location.line(),
+            self.max
+        )
+    }
+}
+
+/// A snapshot of the statistics of the current CPU.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+    count: u64,
+    total: u64,
+    max: u64,
+    histogram: [u64; BUCKETS],
+    #[cfg(feature = "track-caller")]
+    offenders: [Option<Offender>; OFFENDERS],
+}
+
+impl Snapshot {
+    /// Returns how often interrupts were disabled.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the total duration that interrupts were disabled.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the maximum duration that interrupts were disabled.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Returns the histogram of durations with logarithmic buckets.
+    ///
+    /// See [`BUCKETS`] for the bucket boundaries.
+    pub fn histogram(&self) -> &[u64; BUCKETS] {
+        &self.histogram
+    }
+
+    /// Returns the call sites that disabled interrupts for the longest time, worst first.
+    ///
+    /// At most [`OFFENDERS`] call sites are recorded.
+    /// Once all slots are taken, a new call site replaces the recorded call site with the shortest maximum duration if it disabled interrupts for longer.
+    ///
+    /// `Mutex` locks are recorded with a single location inside this crate, since `lock_api` does not propagate `#[track_caller]`.
+    /// Thus, all mutexes share one entry, as do critical sections of `Notify` and `OnceCell`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// for offender in interrupts::stats::snapshot().offenders() {
+    ///     println!("{offender}");
+    /// }
+    /// ```
+    #[cfg(feature = "track-caller")]
+    pub fn offenders(&self) -> impl Iterator<Item = &Offender> {
+        self.offenders.iter().flatten()
+    }
+}
+
+/// Per-CPU statistics.
+pub(crate) struct Stats {
+    #[cfg(feature = "track-caller")]
+    offenders: [Cell<Option<Offender>>; OFFENDERS],
+    count: Cell<u64>,
+    total: Cell<u64>,
+    max: Cell<u64>,
+    histogram: [Cell<u64>; BUCKETS],
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(feature = "track-caller")]
+            offenders: [const { Cell::new(None) }; OFFENDERS],
+            count: Cell::new(0),
+            total: Cell::new(0),
+            max: Cell::new(0),
+            histogram: [const { Cell::new(0) }; BUCKETS],
+        }
+    }
+
+    fn record(&self, section: &Section) {
+        let duration = section.duration;
+        self.count.set(self.count.get() + 1);
+        self.total.set(self.total.get().saturating_add(duration));
+        self.max.set(self.max.get().max(duration));
+        let bucket = &self.histogram[(u64::BITS - duration.leading_zeros()) as usize];
+        bucket.set(bucket.get() + 1);
+
+        #[cfg(feature = "track-caller")]
+        if let Some(location) = section.caller {
+            self.record_offender(location, duration);
+        }
+    }
+
+    #[cfg(feature = "track-caller")]
+    fn record_offender(&self, location: &'static Location<'static>, duration: u64) {
+        let mut victim = &self.offenders[0];
+        for slot in &self.offenders {
+            match slot.get() {
+                Some(mut offender) if offender.location == location => {
+                    offender.count += 1;
+                    offender.max = offender.max.max(duration);
+                    slot.set(Some(offender));
+                    return;
+                }
+                Some(offender) => {
+                    if victim.get().is_some_and(|victim| offender.max < victim.max) {
+                        victim = slot;
+                    }
+                }
+                None => {
+                    if victim.get().is_some() {
+                        victim = slot;
+                    }
+                }
+            }
+        }
+
+        if victim.get().is_none_or(|victim| victim.max < duration) {
+            victim.set(Some(Offender {
+                location,
+                count: 1,
+                max: duration,
+            }));
+        }
+    }
+}
+
+/// Records a finished critical section.
+#[inline]
+pub(crate) fn record(section: &Section) {
+    local::with(|local| local.stats.record(section));
+}
+
+/// Returns a snapshot of the statistics of the current CPU.
+///
+/// On Unix, this returns the statistics of the current thread.
+pub fn snapshot() -> Snapshot {
+    imp::without(|| {
+        local::with(|local| {
+            let stats = &local.stats;
+            Snapshot {
+                count: stats.count.get(),
+                total: stats.total.get(),
+                max: stats.max.get(),
+                histogram: core::array::from_fn(|i| stats.histogram[i].get()),
+                #[cfg(feature = "track-caller")]
+                offenders: {
+                    let mut offenders = core::array::from_fn(|i| stats.offenders[i].get());
+                    offenders.sort_unstable_by_key(|offender: &Option<Offender>| {
+                        core::cmp::Reverse(offender.map(|offender| offender.max))
+                    });
+                    offenders
+                },
+            }
+        })
+    })
+}
+
+/// Resets the statistics of the current CPU.
+///
+/// On Unix, this resets the statistics of the current thread.
+pub fn reset() {
+    imp::without(|| {
+        local::with(|local| {
+            let stats = &local.stats;
+            stats.count.set(0);
+            stats.total.set(0);
+            stats.max.set(0);
+            for bucket in &stats.histogram {
+                bucket.set(0);
+            }
+            #[cfg(feature = "track-caller")]
+            for offender in &stats.offenders {
+                offender.set(None);
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested() {
+        reset();
+
+        crate::without(|| {
+            crate::without(|| {});
+        });
+        let guard = crate::disable();
+        drop(guard);
+
+        let snapshot = snapshot();
+        assert_eq!(snapshot.count(), 2);
+        assert_eq!(snapshot.histogram().iter().sum::<u64>(), 2);
+        assert!(snapshot.total() >= snapshot.max());
+    }
+
+    #[cfg(feature = "track-caller")]
+    #[test]
+    fn offenders() {
+        reset();
+
+        let outer = Location::caller();
+        for _ in 0..2 {
+            crate::without(|| {
+                crate::without(|| {});
+            });
+        }
+
+        let snapshot = snapshot();
+        let offenders = snapshot.offenders().collect::<Vec<_>>();
+        assert_eq!(offenders.len(), 1);
+        assert_eq!(offenders[0].location().file(), outer.file());
+        assert_eq!(offenders[0].location().line(), outer.line() + 2);
+        assert_eq!(offenders[0].count(), 2);
+    }
+}
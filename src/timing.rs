@@ -0,0 +1,83 @@
+//! Timing of the outermost critical section.
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+use core::cell::Cell;
+#[cfg(all(
+    feature = "track-caller",
+    any(feature = "latency-stats", feature = "tracing")
+))]
+use core::panic::Location;
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+use crate::{imp, local};
+
+/// A finished critical section.
+pub(crate) struct Section {
+    /// The duration in timestamp counter ticks.
+    #[cfg(any(feature = "latency-stats", feature = "tracing"))]
+    pub duration: u64,
+    /// The caller that disabled interrupts.
+    #[cfg(all(
+        feature = "track-caller",
+        any(feature = "latency-stats", feature = "tracing")
+    ))]
+    pub caller: Option<&'static Location<'static>>,
+}
+
+/// Per-CPU timing of the current critical section.
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+pub(crate) struct Timing {
+    start: Cell<u64>,
+    #[cfg(feature = "track-caller")]
+    caller: Cell<Option<&'static Location<'static>>>,
+    /// Whether a `tracing` event is being emitted.
+    #[cfg(feature = "tracing")]
+    pub emitting: Cell<bool>,
+}
+
+#[cfg(any(feature = "latency-stats", feature = "tracing"))]
+impl Timing {
+    pub const fn new() -> Self {
+        Self {
+            start: Cell::new(0),
+            #[cfg(feature = "track-caller")]
+            caller: Cell::new(None),
+            #[cfg(feature = "tracing")]
+            emitting: Cell::new(false),
+        }
+    }
+}
+
+/// Records that the outermost guard disabled interrupts.
+#[inline]
+#[cfg_attr(feature = "track-caller", track_caller)]
+pub(crate) fn start() {
+    #[cfg(any(feature = "latency-stats", feature = "tracing"))]
+    {
+        #[cfg(feature = "track-caller")]
+        let caller = Location::caller();
+        let now = imp::timestamp();
+        local::with(|local| {
+            local.timing.start.set(now);
+            #[cfg(feature = "track-caller")]
+            local.timing.caller.set(Some(caller));
+        });
+    }
+}
+
+/// Records that the outermost guard is about to restore interrupts.
+#[inline]
+pub(crate) fn stop() -> Section {
+    #[cfg(any(feature = "latency-stats", feature = "tracing"))]
+    {
+        let now = imp::timestamp();
+        local::with(|local| Section {
+            duration: now.wrapping_sub(local.timing.start.get()),
+            #[cfg(feature = "track-caller")]
+            caller: local.timing.caller.get(),
+        })
+    }
+
+    #[cfg(not(any(feature = "latency-stats", feature = "tracing")))]
+    Section {}
+}
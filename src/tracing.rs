@@ -0,0 +1,112 @@
+//! [`tracing`] integration.
+
+use crate::local;
+use crate::timing::Section;
+
+/// Emits an event for a finished critical section.
+///
+/// The `duration` field is in raw timestamp counter ticks on bare-metal targets and in nanoseconds on Unix.
+/// The `caller` field is the location that created the outermost guard.
+///
+/// This runs after interrupts have been restored, since subscribers may allocate or take locks.
+/// Critical sections entered by the subscriber itself are not reported to avoid infinite recursion.
+#[cold]
+pub(crate) fn section(section: &Section) {
+    if local::with(|local| local.timing.emitting.replace(true)) {
+        return;
+    }
+
+    match section.caller {
+        Some(caller) => ::tracing::trace!(
+            target: "interrupts",
+            duration = section.duration,
+            caller = %caller,
+            "interrupts were disabled"
+        ),
+        None => ::tracing::trace!(
+            target: "interrupts",
+            duration = section.duration,
+            "interrupts were disabled"
+        ),
+    }
+
+    local::with(|local| local.timing.emitting.set(false));
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt;
+    use std::string::String;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use ::tracing::field::{Field, Visit};
+    use ::tracing::span::{Attributes, Id, Record};
+    use ::tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Default)]
+    struct Recorder {
+        callers: Mutex<Vec<String>>,
+    }
+
+    struct CallerVisitor(Option<String>);
+
+    impl Visit for CallerVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "caller" {
+                self.0 = Some(format!("{value:?}"));
+            }
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == "interrupts"
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            // Critical sections of the subscriber itself must not be reported.
+            crate::without(|| {});
+
+            let mut visitor = CallerVisitor(None);
+            event.record(&mut visitor);
+            self.callers.lock().unwrap().push(visitor.0.unwrap());
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn events() {
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let dispatch = ::tracing::Dispatch::from(recorder.clone());
+
+        let line = ::tracing::dispatcher::with_default(&dispatch, || {
+            let (guard, line) = (crate::disable(), line!());
+            // Nested guards do not emit events.
+            crate::without(|| {});
+            drop(guard);
+            crate::without(|| {});
+            line
+        });
+
+        let callers = recorder.callers.lock().unwrap();
+        assert_eq!(callers.len(), 2);
+        let prefix = format!("{}:{}:", file!(), line);
+        assert!(
+            callers[0].starts_with(&prefix),
+            "{} does not start with {prefix}",
+            callers[0]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::hook::Hook;
+
+static DISABLE_HOOK: Hook<fn()> = Hook::new();
+static ENABLE_HOOK: Hook<fn()> = Hook::new();
+
+/// Set the hook that is called when a [`Guard`] disables interrupts.
+///
+/// The hook is only called on actual transitions from enabled to disabled interrupts, not for nested guards.
+/// It runs right after interrupts have been disabled.
+/// Pass `None` to remove the hook.
+///
+/// This is useful for higher layers like quiescent-state detection or tickless timers.
+///
+/// [`Guard`]: crate::Guard
+///
+/// # Examples
+///
+/// ```
+/// fn on_disable() {
+///     // interrupts are disabled
+/// }
+///
+/// interrupts::set_disable_hook(Some(on_disable));
+/// ```
+pub fn set_disable_hook(hook: Option<fn()>) {
+    DISABLE_HOOK.set(hook);
+}
+
+/// Set the hook that is called when a [`Guard`] enables interrupts.
+///
+/// The hook is only called on actual transitions from disabled to enabled interrupts, not for nested guards.
+/// It runs right before interrupts are enabled, while they are still disabled.
+/// Pass `None` to remove the hook.
+///
+/// [`Guard`]: crate::Guard
+///
+/// # Examples
+///
+/// ```
+/// fn on_enable() {
+///     // interrupts are about to be enabled
+/// }
+///
+/// interrupts::set_enable_hook(Some(on_enable));
+/// ```
+pub fn set_enable_hook(hook: Option<fn()>) {
+    ENABLE_HOOK.set(hook);
+}
+
+#[inline]
+pub(crate) fn disabled() {
+    if let Some(hook) = DISABLE_HOOK.get() {
+        hook();
+    }
+}
+
+#[inline]
+pub(crate) fn enabling() {
+    if let Some(hook) = ENABLE_HOOK.get() {
+        hook();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    std::thread_local! {
+        static DISABLED: Cell<usize> = const { Cell::new(0) };
+        static ENABLED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[test]
+    fn transitions() {
+        fn on_disable() {
+            assert!(!crate::are_enabled());
+            DISABLED.set(DISABLED.get() + 1);
+        }
+
+        fn on_enable() {
+            assert!(!crate::are_enabled());
+            ENABLED.set(ENABLED.get() + 1);
+        }
+
+        set_disable_hook(Some(on_disable));
+        set_enable_hook(Some(on_enable));
+
+        crate::without(|| {
+            assert_eq!(DISABLED.get(), 1);
+            crate::without(|| {});
+            assert_eq!(DISABLED.get(), 1);
+            assert_eq!(ENABLED.get(), 0);
+        });
+        assert_eq!(ENABLED.get(), 1);
+
+        set_disable_hook(None);
+        set_enable_hook(None);
+    }
+}
@@ -0,0 +1,240 @@
+//! Unix-specific extensions.
+//!
+//! [`disable`] blocks all signals.
+//! This module allows blocking only a selected set of signals instead.
+//!
+//! [`SigSet`] and [`Signal`] are re-exported from [`nix`] 0.29, which is thus a public dependency of this crate.
+//! Upgrading `nix` to an incompatible version is a breaking change.
+//!
+//! [`disable`]: crate::disable
+//! [`nix`]: https://crates.io/crates/nix
+//!
+//! # Examples
+//!
+//! ```
+//! use interrupts::unix::{SigSet, Signal};
+//!
+//! let mut mask = SigSet::empty();
+//! mask.add(Signal::SIGUSR1);
+//!
+//! // SIGUSR1 may or may not be blocked
+//! let guard = interrupts::unix::disable_with(mask);
+//! // SIGUSR1 is blocked, other signals are unaffected
+//! drop(guard);
+//! // the signal mask is restored to the previous state
+//! ```
+
+use core::marker::PhantomData;
+
+use nix::sys::signal::SigmaskHow;
+pub use nix::sys::signal::{SigSet, Signal};
+
+use crate::{error, Error};
+
+/// Temporarily block the given signals.
+///
+/// The signals in `mask` are added to the signals that are already blocked.
+/// This never unblocks any signals.
+///
+/// The previous signal mask is restored once the returned [`MaskGuard`] is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use interrupts::unix::{SigSet, Signal};
+///
+/// let mut mask = SigSet::empty();
+/// mask.add(Signal::SIGUSR1);
+///
+/// // SIGUSR1 may or may not be blocked
+/// let guard = interrupts::unix::disable_with(mask);
+/// // SIGUSR1 is blocked
+/// drop(guard);
+/// // the signal mask is restored to the previous state
+/// ```
+///
+/// # Panics
+///
+/// Panics if the signal mask could not be changed.
+/// Use [`try_disable_with`] to handle this error instead.
+#[inline]
+pub fn disable_with(mask: SigSet) -> MaskGuard {
+    match try_disable_with(mask) {
+        Ok(guard) => guard,
+        Err(err) => panic!("failed to block signals: {err}"),
+    }
+}
+
+/// Temporarily block the given signals, returning an error on failure.
+///
+/// See [`disable_with`] for details.
+///
+/// # Errors
+///
+/// Returns an error if the signal mask could not be changed.
+#[inline]
+pub fn try_disable_with(mask: SigSet) -> Result<MaskGuard, Error> {
+    let old_mask = mask
+        .thread_swap_mask(SigmaskHow::SIG_BLOCK)
+        .map_err(Error)?;
+    Ok(MaskGuard {
+        old_mask,
+        _not_send: PhantomData,
+    })
+}
+
+/// A signal mask guard.
+///
+/// Created using [`disable_with`].
+///
+/// While an instance of this guard is held, the signals passed to [`disable_with`] are blocked.
+/// When this guard is dropped, the signal mask is restored to the exact state before blocking.
+///
+/// # Caveats (Drop Order)
+///
+/// Like [`Guard`], mask guards restore the signal mask that was active when they were created.
+/// Dropping different guards in the wrong order may unblock signals, even though another guard is still held.
+/// See [`Guard`'s caveats] for details.
+///
+/// Errors when restoring the signal mask are handled like [`Guard`'s].
+///
+/// [`Guard`]: crate::Guard
+/// [`Guard`'s caveats]: crate::Guard#caveats-drop-order
+/// [`Guard`'s]: crate::Guard#errors-when-restoring
+pub struct MaskGuard {
+    old_mask: SigSet,
+    /// Signal masks are per thread.
+    ///
+    /// Making MaskGuard `!Send` avoids blocking signals on one thread and restoring on another.
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl MaskGuard {
+    /// ```compile_fail
+    /// fn send<T: Send>(_: T) {}
+    ///
+    /// send(interrupts::unix::disable_with(interrupts::unix::SigSet::empty()));
+    /// ```
+    fn _dummy() {}
+}
+
+impl Drop for MaskGuard {
+    #[inline]
+    fn drop(&mut self) {
+        if let Err(err) = self.old_mask.thread_set_mask() {
+            error::restore_failed(Error(err));
+        }
+    }
+}
+
+/// Run a closure with the given signals blocked.
+///
+/// Run the given closure, blocking the signals in `mask` before running it.
+/// Afterward, the signal mask is restored to the previous state.
+///
+/// # Examples
+///
+/// ```
+/// use interrupts::unix::{SigSet, Signal};
+///
+/// let mut mask = SigSet::empty();
+/// mask.add(Signal::SIGUSR1);
+///
+/// // SIGUSR1 may or may not be blocked
+/// interrupts::unix::without_signals(mask, || {
+///     // SIGUSR1 is blocked
+/// });
+/// // the signal mask is restored to the previous state
+/// ```
+#[inline]
+pub fn without_signals<F, R>(mask: SigSet, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let guard = disable_with(mask);
+
+    let ret = f();
+
+    drop(guard);
+
+    ret
+}
+
+/// Install a signal handler that is deferred while signals are disabled.
+///
+/// With the `lazy-signals` feature, [`disable`] does not change the signal mask.
+/// Instead, signals with handlers installed via this function are recorded as pending while signals are disabled.
+/// Their handlers are run once the outermost [`Guard`] is dropped.
+///
+/// Handlers always run with signals disabled.
+/// Pass `None` to restore the default action.
+///
+/// [`disable`]: crate::disable
+/// [`Guard`]: crate::Guard
+///
+/// # Safety
+///
+/// `handler` may run in a signal handler and must thus be async-signal-safe.
+///
+/// # Errors
+///
+/// Returns an error if the signal action could not be changed.
+///
+/// # Panics
+///
+/// Panics if `signal` does not fit into the pending signal bitmap (signal numbers 64 and above).
+///
+/// # Examples
+///
+/// ```
+/// use interrupts::unix::Signal;
+///
+/// fn handle_sigusr1(_signal: Signal) {
+///     // signals are disabled
+/// }
+///
+/// unsafe { interrupts::unix::set_handler(Signal::SIGUSR1, Some(handle_sigusr1))? };
+/// # Ok::<(), interrupts::Error>(())
+/// ```
+#[cfg(feature = "lazy-signals")]
+pub unsafe fn set_handler(signal: Signal, handler: Option<fn(Signal)>) -> Result<(), Error> {
+    unsafe { crate::imp::set_handler(signal, handler) }.map_err(Error)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn selective() {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        use nix::libc;
+        use nix::sys::signal::{self, SigHandler};
+
+        use super::*;
+
+        static USR1_RAN: AtomicBool = AtomicBool::new(false);
+        static USR2_RAN: AtomicBool = AtomicBool::new(false);
+
+        extern "C" fn handle_sigusr1(_signal: libc::c_int) {
+            USR1_RAN.store(true, Ordering::Relaxed);
+        }
+
+        extern "C" fn handle_sigusr2(_signal: libc::c_int) {
+            USR2_RAN.store(true, Ordering::Relaxed);
+        }
+
+        unsafe { signal::signal(Signal::SIGUSR1, SigHandler::Handler(handle_sigusr1)) }.unwrap();
+        unsafe { signal::signal(Signal::SIGUSR2, SigHandler::Handler(handle_sigusr2)) }.unwrap();
+
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGUSR1);
+
+        let guard = disable_with(mask);
+        signal::raise(Signal::SIGUSR1).unwrap();
+        signal::raise(Signal::SIGUSR2).unwrap();
+        assert!(!USR1_RAN.load(Ordering::Relaxed));
+        assert!(USR2_RAN.load(Ordering::Relaxed));
+        drop(guard);
+        assert!(USR1_RAN.load(Ordering::Relaxed));
+    }
+}